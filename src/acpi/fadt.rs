@@ -0,0 +1,166 @@
+// FADT(Fixed ACPI Description Table)，签名为 "FACP"
+// 描述了固定的电源管理硬件：PM1控制寄存器(关机)、复位寄存器(重启)、SCI中断号、DSDT的地址等。
+// ACPI 2.0在表的后半部分增加了64位的 `X_` 字段，存在且非零时优先于对应的32位字段
+
+use super::{read_u16, read_u32, read_u64, GenericAddress};
+
+// 各字段在表中的偏移
+const FIRMWARE_CTRL: usize = 36;
+const DSDT: usize = 40;
+const SCI_INT: usize = 46;
+const SMI_CMD: usize = 48;
+const ACPI_ENABLE: usize = 52;
+const ACPI_DISABLE: usize = 53;
+const PM1A_EVT_BLK: usize = 56;
+const PM1B_EVT_BLK: usize = 60;
+const PM1A_CNT_BLK: usize = 64;
+const PM1B_CNT_BLK: usize = 68;
+const PM_TMR_BLK: usize = 76;
+const PM1_EVT_LEN: usize = 88;
+const PM1_CNT_LEN: usize = 89;
+const CENTURY: usize = 108;
+const IAPC_BOOT_ARCH: usize = 109;
+const FLAGS: usize = 112;
+const RESET_REG: usize = 116;
+const RESET_VALUE: usize = 128;
+const X_DSDT: usize = 140;
+const X_PM1A_CNT_BLK: usize = 172;
+const X_PM1B_CNT_BLK: usize = 184;
+
+// 标志位：支持复位寄存器
+const RESET_REG_SUP: u32 = 1 << 10;
+// IA-PC启动架构标志：存在8042键盘控制器
+const BOOT_ARCH_8042: u16 = 1 << 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fadt {
+    pub revision: u8,
+    pub firmware_ctrl: u32,
+    // DSDT的物理地址，其中的 `\_S5` 对象给出了关机时写入PM1控制寄存器的睡眠类型
+    pub dsdt: u64,
+    // SCI(系统控制中断)使用的ISA中断号
+    pub sci_interrupt: u16,
+    // 写入 `acpi_enable` 到这个端口可以让固件把控制权交给操作系统，为0表示已经处于ACPI模式
+    pub smi_command_port: u32,
+    pub acpi_enable: u8,
+    pub acpi_disable: u8,
+    pub pm1a_event_block: u32,
+    pub pm1b_event_block: u32,
+    pub pm1_event_length: u8,
+    // PM1控制寄存器，关机时写入睡眠类型和SLP_EN位，地址为0表示不存在
+    pub pm1a_control_block: GenericAddress,
+    pub pm1b_control_block: GenericAddress,
+    pub pm1_control_length: u8,
+    // ACPI电源管理定时器的I/O端口，3.579545 MHz
+    pub pm_timer_block: u32,
+    // RTC中“世纪”寄存器的CMOS下标，0表示不存在
+    pub century_register: u8,
+    // 是否存在8042键盘控制器(ACPI 2.0之前的表中没有这个字段，视为存在)
+    pub has_8042: bool,
+    pub flags: u32,
+    // 复位寄存器，`flags` 中的RESET_REG_SUP位表示它是否可用
+    pub reset_register: Option<GenericAddress>,
+    pub reset_value: u8,
+}
+
+// 解析完整的FADT(包括表头)，表太短时返回 `None`
+pub fn parse(table: &[u8]) -> Option<Fadt> {
+    if table.len() < PM1_CNT_LEN + 1 {
+        return None;
+    }
+    let revision = table[8];
+    let has = |offset: usize, len: usize| table.len() >= offset + len;
+    // 64位字段存在并且非零时才使用，否则退回到32位字段
+    let extended = |offset: usize, legacy: u32| -> u64 {
+        if has(offset, 8) && read_u64(table, offset) != 0 {
+            read_u64(table, offset)
+        } else {
+            u64::from(legacy)
+        }
+    };
+    // X_PM1x_CNT_BLK是通用地址结构，可能位于I/O端口或MMIO。
+    // 地址为0或者位于其他地址空间时退回到32位字段，32位字段总是I/O端口
+    let extended_gas = |offset: usize, legacy: u32| -> GenericAddress {
+        if has(offset, 12) {
+            let gas = GenericAddress::parse(table, offset);
+            let supported = matches!(gas.address_space, GenericAddress::SYSTEM_IO | GenericAddress::SYSTEM_MEMORY);
+            if gas.address != 0 && supported {
+                return gas;
+            }
+        }
+        GenericAddress {
+            address_space: GenericAddress::SYSTEM_IO,
+            bit_width: 16,
+            bit_offset: 0,
+            access_size: 2,
+            address: u64::from(legacy),
+        }
+    };
+
+    let flags = if has(FLAGS, 4) { read_u32(table, FLAGS) } else { 0 };
+    let reset_register = if has(RESET_VALUE, 1) && flags & RESET_REG_SUP != 0 {
+        Some(GenericAddress::parse(table, RESET_REG))
+    } else {
+        None
+    };
+
+    Some(Fadt {
+        revision,
+        firmware_ctrl: read_u32(table, FIRMWARE_CTRL),
+        dsdt: extended(X_DSDT, read_u32(table, DSDT)),
+        sci_interrupt: read_u16(table, SCI_INT),
+        smi_command_port: read_u32(table, SMI_CMD),
+        acpi_enable: table[ACPI_ENABLE],
+        acpi_disable: table[ACPI_DISABLE],
+        pm1a_event_block: read_u32(table, PM1A_EVT_BLK),
+        pm1b_event_block: read_u32(table, PM1B_EVT_BLK),
+        pm1_event_length: table[PM1_EVT_LEN],
+        pm1a_control_block: extended_gas(X_PM1A_CNT_BLK, read_u32(table, PM1A_CNT_BLK)),
+        pm1b_control_block: extended_gas(X_PM1B_CNT_BLK, read_u32(table, PM1B_CNT_BLK)),
+        pm1_control_length: table[PM1_CNT_LEN],
+        pm_timer_block: read_u32(table, PM_TMR_BLK),
+        century_register: if has(CENTURY, 1) { table[CENTURY] } else { 0 },
+        has_8042: revision < 2 || !has(IAPC_BOOT_ARCH, 2) || read_u16(table, IAPC_BOOT_ARCH) & BOOT_ARCH_8042 != 0,
+        flags,
+        reset_register,
+        reset_value: if has(RESET_VALUE, 1) { table[RESET_VALUE] } else { 0 },
+    })
+}
+
+#[test_case]
+fn test_parse_fadt() {
+    let mut table = [0u8; 244];
+    table[0..4].copy_from_slice(b"FACP");
+    table[8] = 3;
+    table[DSDT..DSDT + 4].copy_from_slice(&0x1000u32.to_le_bytes());
+    table[SCI_INT..SCI_INT + 2].copy_from_slice(&9u16.to_le_bytes());
+    table[PM1A_CNT_BLK..PM1A_CNT_BLK + 4].copy_from_slice(&0x604u32.to_le_bytes());
+    table[PM1_CNT_LEN] = 2;
+    table[CENTURY] = 0x32;
+    table[FLAGS..FLAGS + 4].copy_from_slice(&RESET_REG_SUP.to_le_bytes());
+    // 复位寄存器：I/O端口0xcf9，写入0x06
+    table[RESET_REG] = GenericAddress::SYSTEM_IO;
+    table[RESET_REG + 1] = 8;
+    table[RESET_REG + 4..RESET_REG + 12].copy_from_slice(&0xcf9u64.to_le_bytes());
+    table[RESET_VALUE] = 0x06;
+    // 64位DSDT地址优先
+    table[X_DSDT..X_DSDT + 8].copy_from_slice(&0x2000u64.to_le_bytes());
+    // PM1b的扩展字段位于MMIO，保留完整的通用地址结构
+    table[X_PM1B_CNT_BLK] = GenericAddress::SYSTEM_MEMORY;
+    table[X_PM1B_CNT_BLK + 4..X_PM1B_CNT_BLK + 12].copy_from_slice(&0xfed0_0000u64.to_le_bytes());
+
+    let fadt = parse(&table).expect("FADT too short");
+    assert_eq!(fadt.dsdt, 0x2000);
+    assert_eq!(fadt.sci_interrupt, 9);
+    // 扩展字段为空时使用32位的I/O端口
+    let pm1a = fadt.pm1a_control_block;
+    assert_eq!((pm1a.address_space, pm1a.address), (GenericAddress::SYSTEM_IO, 0x604));
+    let pm1b = fadt.pm1b_control_block;
+    assert_eq!((pm1b.address_space, pm1b.address), (GenericAddress::SYSTEM_MEMORY, 0xfed0_0000));
+    assert_eq!(fadt.century_register, 0x32);
+    assert!(!fadt.has_8042);
+    let reset = fadt.reset_register.expect("reset register missing");
+    assert_eq!((reset.address_space, reset.address), (GenericAddress::SYSTEM_IO, 0xcf9));
+    assert_eq!(fadt.reset_value, 0x06);
+    assert!(parse(&table[..super::SDT_HEADER_SIZE]).is_none());
+}
@@ -0,0 +1,64 @@
+// HPET表，签名为 "HPET"
+// 描述高精度事件定时器(High Precision Event Timer)的寄存器地址和能力
+
+use super::{read_u16, read_u32, GenericAddress, SDT_HEADER_SIZE};
+
+// 表的最小长度
+const HPET_TABLE_SIZE: usize = 56;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hpet {
+    // 硬件版本号
+    pub hardware_revision: u8,
+    // 比较器(定时器)的数量
+    pub comparator_count: u8,
+    // 主计数器是否为64位
+    pub counter_64bit: bool,
+    // 是否支持替代传统PIT和RTC中断的“传统替换路由”
+    pub legacy_replacement: bool,
+    pub pci_vendor_id: u16,
+    // 寄存器所在的地址，通常位于系统内存(MMIO)
+    pub base_address: GenericAddress,
+    // 系统中的第几个HPET
+    pub hpet_number: u8,
+    // 周期模式下不丢中断的最小时钟数
+    pub minimum_tick: u16,
+}
+
+// 解析完整的HPET表(包括表头)，表太短时返回 `None`
+pub fn parse(table: &[u8]) -> Option<Hpet> {
+    if table.len() < HPET_TABLE_SIZE {
+        return None;
+    }
+    let id = read_u32(table, SDT_HEADER_SIZE);
+    Some(Hpet {
+        hardware_revision: id as u8,
+        comparator_count: ((id >> 8) & 0x1f) as u8 + 1,
+        counter_64bit: id & (1 << 13) != 0,
+        legacy_replacement: id & (1 << 15) != 0,
+        pci_vendor_id: (id >> 16) as u16,
+        base_address: GenericAddress::parse(table, SDT_HEADER_SIZE + 4),
+        hpet_number: table[SDT_HEADER_SIZE + 16],
+        minimum_tick: read_u16(table, SDT_HEADER_SIZE + 17),
+    })
+}
+
+#[test_case]
+fn test_parse_hpet() {
+    let mut table = [0u8; HPET_TABLE_SIZE];
+    table[0..4].copy_from_slice(b"HPET");
+    // 版本1，3个比较器，64位计数器，支持传统替换路由，厂商0x8086
+    let id: u32 = 0x8086_0000 | (1 << 15) | (1 << 13) | (2 << 8) | 1;
+    table[SDT_HEADER_SIZE..SDT_HEADER_SIZE + 4].copy_from_slice(&id.to_le_bytes());
+    table[SDT_HEADER_SIZE + 8..SDT_HEADER_SIZE + 16].copy_from_slice(&0xfed0_0000u64.to_le_bytes());
+    table[SDT_HEADER_SIZE + 17..SDT_HEADER_SIZE + 19].copy_from_slice(&128u16.to_le_bytes());
+
+    let hpet = parse(&table).expect("HPET table too short");
+    assert_eq!(hpet.hardware_revision, 1);
+    assert_eq!(hpet.comparator_count, 3);
+    assert!(hpet.counter_64bit && hpet.legacy_replacement);
+    assert_eq!(hpet.pci_vendor_id, 0x8086);
+    assert_eq!(hpet.base_address.address_space, GenericAddress::SYSTEM_MEMORY);
+    assert_eq!(hpet.base_address.address, 0xfed0_0000);
+    assert_eq!(hpet.minimum_tick, 128);
+}
@@ -0,0 +1,132 @@
+// MADT(Multiple APIC Description Table)，签名为 "APIC"
+// 列出了本地APIC的物理地址、每个CPU的本地APIC、所有IO APIC，以及ISA中断到全局系统中断(GSI)的重定向
+
+use alloc::vec::Vec;
+
+use super::{read_u16, read_u32, read_u64, SDT_HEADER_SIZE};
+
+// 标志位：系统中同时存在兼容的双8259 PIC，切换到APIC时需要屏蔽它们
+const PCAT_COMPAT: u32 = 1;
+// 处理器本地APIC条目的标志位：处理器可用
+const PROCESSOR_ENABLED: u32 = 1;
+// 处理器本地APIC条目的标志位：处理器当前不可用，但可以在运行时上线
+const PROCESSOR_ONLINE_CAPABLE: u32 = 1 << 1;
+
+// 一个处理器及其本地APIC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessorEntry {
+    pub processor_id: u8,
+    pub apic_id: u8,
+    // 处理器可用，或者可以在运行时启动
+    pub usable: bool,
+}
+
+// MADT中的一个IO APIC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoApicEntry {
+    pub id: u8,
+    // 寄存器的物理地址
+    pub address: u32,
+    // 它的第一个重定向项对应的全局系统中断号
+    pub gsi_base: u32,
+}
+
+// ISA中断重定向：ISA中断 `source` 实际连接到全局系统中断 `gsi`，并使用 `flags` 中的极性和触发方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptOverride {
+    pub source: u8,
+    pub gsi: u32,
+    // MPS INTI标志：第0-1位为极性，第2-3位为触发方式
+    pub flags: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Madt {
+    // 本地APIC寄存器的物理地址
+    pub local_apic_address: u64,
+    // 是否同时存在8259 PIC
+    pub pcat_compat: bool,
+    pub processors: Vec<ProcessorEntry>,
+    pub io_apics: Vec<IoApicEntry>,
+    pub overrides: Vec<InterruptOverride>,
+}
+
+// 解析完整的MADT(包括表头)
+pub fn parse(table: &[u8]) -> Madt {
+    let mut madt = Madt {
+        local_apic_address: u64::from(read_u32(table, SDT_HEADER_SIZE)),
+        pcat_compat: read_u32(table, SDT_HEADER_SIZE + 4) & PCAT_COMPAT != 0,
+        processors: Vec::new(),
+        io_apics: Vec::new(),
+        overrides: Vec::new(),
+    };
+
+    // 表头和两个32位字段(本地APIC地址、标志)之后是变长的条目，每个条目以类型和长度开头
+    let mut offset = SDT_HEADER_SIZE + 8;
+    while offset + 2 <= table.len() {
+        let entry_type = table[offset];
+        let length = usize::from(table[offset + 1]);
+        if length < 2 || offset + length > table.len() {
+            break;
+        }
+        let entry = &table[offset..offset + length];
+        match entry_type {
+            // 处理器本地APIC
+            0 if length >= 8 => {
+                let flags = read_u32(entry, 4);
+                madt.processors.push(ProcessorEntry {
+                    processor_id: entry[2],
+                    apic_id: entry[3],
+                    usable: flags & (PROCESSOR_ENABLED | PROCESSOR_ONLINE_CAPABLE) != 0,
+                });
+            }
+            // IO APIC
+            1 if length >= 12 => madt.io_apics.push(IoApicEntry {
+                id: entry[2],
+                address: read_u32(entry, 4),
+                gsi_base: read_u32(entry, 8),
+            }),
+            // 中断源重定向
+            2 if length >= 10 => madt.overrides.push(InterruptOverride {
+                source: entry[3],
+                gsi: read_u32(entry, 4),
+                flags: read_u16(entry, 8),
+            }),
+            // 64位本地APIC地址，覆盖表头中的32位地址
+            5 if length >= 12 => madt.local_apic_address = read_u64(entry, 4),
+            _ => {}
+        }
+        offset += length;
+    }
+    madt
+}
+
+#[test_case]
+fn test_parse_madt() {
+    let mut table = [0u8; SDT_HEADER_SIZE + 8 + 8 + 12 + 10];
+    table[0..4].copy_from_slice(b"APIC");
+    table[SDT_HEADER_SIZE..SDT_HEADER_SIZE + 4].copy_from_slice(&0xfee0_0000u32.to_le_bytes());
+    table[SDT_HEADER_SIZE + 4] = PCAT_COMPAT as u8;
+    // 处理器0，APIC ID 0，可用
+    let cpu = SDT_HEADER_SIZE + 8;
+    table[cpu + 1] = 8;
+    table[cpu + 4] = PROCESSOR_ENABLED as u8;
+    // IO APIC: ID 1，地址0xfec00000，GSI从0开始
+    let io = cpu + 8;
+    table[io] = 1;
+    table[io + 1] = 12;
+    table[io + 2] = 1;
+    table[io + 4..io + 8].copy_from_slice(&0xfec0_0000u32.to_le_bytes());
+    // 中断源重定向：ISA IRQ0 -> GSI 2
+    let ovr = io + 12;
+    table[ovr] = 2;
+    table[ovr + 1] = 10;
+    table[ovr + 4..ovr + 8].copy_from_slice(&2u32.to_le_bytes());
+
+    let madt = parse(&table);
+    assert_eq!(madt.local_apic_address, 0xfee0_0000);
+    assert!(madt.pcat_compat);
+    assert_eq!(madt.processors, [ProcessorEntry { processor_id: 0, apic_id: 0, usable: true }]);
+    assert_eq!(madt.io_apics, [IoApicEntry { id: 1, address: 0xfec0_0000, gsi_base: 0 }]);
+    assert_eq!(madt.overrides, [InterruptOverride { source: 0, gsi: 2, flags: 0 }]);
+}
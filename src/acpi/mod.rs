@@ -0,0 +1,309 @@
+// ACPI表
+// 固件通过ACPI表描述平台硬件：中断控制器(MADT)、电源管理寄存器(FADT)、高精度事件定时器(HPET)等。查找过程：
+// 1. 在EBDA(扩展BIOS数据区)的前1KiB或BIOS只读区域(0xE0000-0xFFFFF)中，按16字节对齐查找签名为 "RSD PTR " 的RSDP；
+// 2. ACPI 2.0及以上使用RSDP中64位的XSDT地址，否则使用32位的RSDT地址；
+// 3. RSDT/XSDT的表头之后是其他所有表的物理地址，每张表以36字节的通用表头开始，前4个字节是签名。
+// 每个结构都带有校验和，所有字节相加(模256)为0，校验失败的表会被忽略。
+// 各个表被解析成普通的Rust结构体保存下来，使用时不需要再访问物理内存
+
+use alloc::vec::Vec;
+use core::slice;
+use spin::Once;
+use x86_64::PhysAddr;
+
+use crate::memory::paging;
+
+pub mod fadt;
+pub mod hpet;
+pub mod madt;
+
+// BDA中保存EBDA段地址的位置
+const EBDA_SEGMENT_PTR: u64 = 0x40e;
+// 在EBDA中查找RSDP的范围
+const EBDA_SEARCH_SIZE: u64 = 1024;
+// BIOS只读区域，RSDP位于其中某个16字节对齐的位置
+const BIOS_AREA_START: u64 = 0xe0000;
+const BIOS_AREA_END: u64 = 0x100000;
+// ACPI 1.0的RSDP长度，校验和只覆盖这一部分
+const RSDP_V1_SIZE: usize = 20;
+// ACPI 2.0的RSDP长度，扩展校验和覆盖整个结构
+const RSDP_V2_SIZE: usize = 36;
+// 所有系统描述表共同的表头长度
+pub const SDT_HEADER_SIZE: usize = 36;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcpiError {
+    // EBDA和BIOS区域中都没有有效的RSDP
+    RsdpNotFound,
+    // RSDT/XSDT的签名或校验和不正确
+    InvalidRootTable,
+}
+
+// RSDP(根系统描述指针)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rsdp {
+    // RSDP所在的物理地址
+    pub address: u64,
+    // 0表示ACPI 1.0，2表示ACPI 2.0及以上
+    pub revision: u8,
+    pub oem_id: [u8; 6],
+    pub rsdt_address: u32,
+    // 只有ACPI 2.0及以上才有XSDT
+    pub xsdt_address: Option<u64>,
+}
+
+// 通用的系统描述表表头
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SdtHeader {
+    pub signature: [u8; 4],
+    pub length: u32,
+    pub revision: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+}
+
+impl SdtHeader {
+    // 从表的开头解析表头，长度不足时返回 `None`
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < SDT_HEADER_SIZE {
+            return None;
+        }
+        Some(SdtHeader {
+            signature: bytes[0..4].try_into().unwrap(),
+            length: read_u32(bytes, 4),
+            revision: bytes[8],
+            oem_id: bytes[10..16].try_into().unwrap(),
+            oem_table_id: bytes[16..24].try_into().unwrap(),
+            oem_revision: read_u32(bytes, 24),
+        })
+    }
+}
+
+// 通用地址结构(GAS)，描述一个寄存器位于哪个地址空间以及它的地址
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenericAddress {
+    // 0为系统内存(MMIO)，1为系统I/O端口
+    pub address_space: u8,
+    pub bit_width: u8,
+    pub bit_offset: u8,
+    pub access_size: u8,
+    pub address: u64,
+}
+
+impl GenericAddress {
+    pub const SYSTEM_MEMORY: u8 = 0;
+    pub const SYSTEM_IO: u8 = 1;
+
+    // 从 `offset` 处解析12字节的通用地址结构
+    pub fn parse(bytes: &[u8], offset: usize) -> Self {
+        GenericAddress {
+            address_space: bytes[offset],
+            bit_width: bytes[offset + 1],
+            bit_offset: bytes[offset + 2],
+            access_size: bytes[offset + 3],
+            address: read_u64(bytes, offset + 4),
+        }
+    }
+}
+
+// 解析后的ACPI表
+#[derive(Debug)]
+pub struct AcpiTables {
+    pub rsdp: Rsdp,
+    // RSDT/XSDT中列出的所有通过校验的表，以及它们的物理地址
+    pub tables: Vec<(SdtHeader, u64)>,
+    pub madt: Option<madt::Madt>,
+    pub fadt: Option<fadt::Fadt>,
+    pub hpet: Option<hpet::Hpet>,
+}
+
+impl AcpiTables {
+    // 按签名查找表的物理地址，例如 `b"SSDT"`、`b"MCFG"`
+    pub fn find(&self, signature: &[u8; 4]) -> Option<u64> {
+        self.tables
+            .iter()
+            .find(|(header, _)| &header.signature == signature)
+            .map(|&(_, address)| address)
+    }
+}
+
+static ACPI: Once<AcpiTables> = Once::new();
+
+// 查找并解析ACPI表，只在第一次调用时真正执行，之后返回同一份结果
+// 需要在 `memory::init` 和 `allocator::init_heap` 之后调用，通过物理内存的直接映射读取ACPI表
+pub fn init() -> Result<&'static AcpiTables, AcpiError> {
+    ACPI.try_call_once(|| {
+        let rsdp = find_rsdp().ok_or(AcpiError::RsdpNotFound)?;
+        let tables = unsafe { root_table_entries(&rsdp)? };
+
+        let mut acpi = AcpiTables {
+            rsdp,
+            tables: Vec::new(),
+            madt: None,
+            fadt: None,
+            hpet: None,
+        };
+        for address in tables {
+            let bytes = unsafe { sdt_bytes(address) };
+            let header = match SdtHeader::parse(bytes) {
+                Some(header) if checksum_ok(bytes) => header,
+                _ => continue,
+            };
+            match &header.signature {
+                b"APIC" => acpi.madt = Some(madt::parse(bytes)),
+                b"FACP" => acpi.fadt = fadt::parse(bytes),
+                b"HPET" => acpi.hpet = hpet::parse(bytes),
+                _ => {}
+            }
+            acpi.tables.push((header, address));
+        }
+        Ok(acpi)
+    })
+}
+
+// 已经解析过的ACPI表，`init` 之前或失败时返回 `None`
+pub fn tables() -> Option<&'static AcpiTables> {
+    ACPI.get()
+}
+
+// 依次在EBDA的前1KiB和BIOS只读区域中查找RSDP
+fn find_rsdp() -> Option<Rsdp> {
+    // BDA中的EBDA段地址左移4位得到物理地址
+    let ebda = u64::from(read_u16(unsafe { phys_bytes(EBDA_SEGMENT_PTR, 2) }, 0)) << 4;
+    let ebda_range = if ebda != 0 { ebda..ebda + EBDA_SEARCH_SIZE } else { 0..0 };
+    ebda_range
+        .step_by(16)
+        .chain((BIOS_AREA_START..BIOS_AREA_END).step_by(16))
+        .find_map(|address| parse_rsdp(unsafe { phys_bytes(address, RSDP_V2_SIZE) }, address))
+}
+
+// 验证签名和校验和并解析RSDP
+fn parse_rsdp(bytes: &[u8], address: u64) -> Option<Rsdp> {
+    if &bytes[0..8] != b"RSD PTR " || !checksum_ok(&bytes[..RSDP_V1_SIZE]) {
+        return None;
+    }
+    let revision = bytes[15];
+    let xsdt_address = if revision >= 2 {
+        // ACPI 2.0的扩展部分有自己的校验和
+        let length = (read_u32(bytes, 20) as usize).clamp(RSDP_V1_SIZE, RSDP_V2_SIZE);
+        if !checksum_ok(&bytes[..length]) {
+            return None;
+        }
+        Some(read_u64(bytes, 24))
+    } else {
+        None
+    };
+    Some(Rsdp {
+        address,
+        revision,
+        oem_id: bytes[9..15].try_into().unwrap(),
+        rsdt_address: read_u32(bytes, 16),
+        xsdt_address,
+    })
+}
+
+// 读取RSDT或XSDT中列出的所有表的物理地址
+/// # Safety
+///
+/// 调用者必须保证RSDP中的地址指向有效的物理内存
+unsafe fn root_table_entries(rsdp: &Rsdp) -> Result<Vec<u64>, AcpiError> {
+    // ACPI 2.0及以上的XSDT使用64位指针，否则使用RSDT的32位指针
+    let (address, signature, entry_size) = match rsdp.xsdt_address {
+        Some(xsdt) if xsdt != 0 => (xsdt, b"XSDT", 8),
+        _ => (u64::from(rsdp.rsdt_address), b"RSDT", 4),
+    };
+    let bytes = sdt_bytes(address);
+    if &bytes[0..4] != signature || !checksum_ok(bytes) {
+        return Err(AcpiError::InvalidRootTable);
+    }
+    Ok(bytes[SDT_HEADER_SIZE..]
+        .chunks_exact(entry_size)
+        .map(|entry| {
+            if entry_size == 8 {
+                read_u64(entry, 0)
+            } else {
+                u64::from(read_u32(entry, 0))
+            }
+        })
+        .collect())
+}
+
+// ACPI结构的所有字节相加(模256)应该为0
+pub fn checksum_ok(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) == 0
+}
+
+// 读取物理地址 `address` 处的一张系统描述表，长度取自表头
+/// # Safety
+///
+/// 调用者必须保证 `address` 处确实是一张ACPI表(例如来自RSDT/XSDT或FADT中的指针)，
+/// 并且这段物理内存在返回的切片存活期间不会被修改
+pub unsafe fn sdt_bytes(address: u64) -> &'static [u8] {
+    let header = phys_bytes(address, SDT_HEADER_SIZE);
+    let length = read_u32(header, 4) as usize;
+    phys_bytes(address, length.max(SDT_HEADER_SIZE))
+}
+
+// 通过物理内存的直接映射访问 `address` 开始的 `len` 个字节
+/// # Safety
+///
+/// 调用者必须保证这段物理内存存在，并且在返回的切片存活期间不会被修改
+unsafe fn phys_bytes(address: u64, len: usize) -> &'static [u8] {
+    let virt = paging::phys_to_virt(PhysAddr::new(address));
+    slice::from_raw_parts(virt.as_ptr::<u8>(), len)
+}
+
+// 按小端序读取，ACPI表中的所有多字节字段都是小端序
+pub(crate) fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+pub(crate) fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+pub(crate) fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+// 测试用：把校验和字段设置为使所有字节之和为0的值
+#[cfg(test)]
+fn fix_checksum(bytes: &mut [u8], checksum_offset: usize) {
+    bytes[checksum_offset] = 0;
+    let sum = bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+    bytes[checksum_offset] = 0u8.wrapping_sub(sum);
+}
+
+#[test_case]
+fn test_parse_rsdp() {
+    let mut bytes = [0u8; RSDP_V2_SIZE];
+    bytes[0..8].copy_from_slice(b"RSD PTR ");
+    bytes[9..15].copy_from_slice(b"BOCHS ");
+    bytes[15] = 2;
+    bytes[16..20].copy_from_slice(&0x1234u32.to_le_bytes());
+    bytes[20..24].copy_from_slice(&(RSDP_V2_SIZE as u32).to_le_bytes());
+    bytes[24..32].copy_from_slice(&0x5678u64.to_le_bytes());
+    fix_checksum(&mut bytes[..RSDP_V1_SIZE], 8);
+    fix_checksum(&mut bytes, 32);
+
+    let rsdp = parse_rsdp(&bytes, 0xf0000).expect("valid RSDP rejected");
+    assert_eq!(rsdp.revision, 2);
+    assert_eq!(&rsdp.oem_id, b"BOCHS ");
+    assert_eq!(rsdp.rsdt_address, 0x1234);
+    assert_eq!(rsdp.xsdt_address, Some(0x5678));
+
+    // 校验和错误的RSDP被拒绝
+    bytes[16] ^= 1;
+    assert_eq!(parse_rsdp(&bytes, 0xf0000), None);
+}
+
+#[test_case]
+fn test_acpi_tables_found() {
+    // QEMU总是提供ACPI表，其中包括MADT和FADT
+    let acpi = init().expect("ACPI tables not found");
+    assert!(acpi.find(b"APIC").is_some());
+    let madt = acpi.madt.as_ref().expect("MADT missing");
+    assert!(!madt.io_apics.is_empty());
+    assert!(acpi.fadt.is_some());
+}
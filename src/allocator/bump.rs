@@ -0,0 +1,117 @@
+// 碰撞分配器(bump allocator)
+// 用 `next` 指向第一个未使用的字节，每次分配都把它向前移动。只记录未释放的分配数量，数量归零时才把 `next` 重置到堆的起始处，
+// 所以只要还有一个长期存活的对象，已释放的内存就都无法重用
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr;
+use x86_64::instructions::interrupts;
+
+use super::{align_up, Locked};
+
+pub struct BumpAllocator {
+    heap_start: usize,
+    heap_end: usize,
+    // 下一次分配的起始地址
+    next: usize,
+    // 尚未释放的分配数量
+    allocations: usize,
+}
+
+impl BumpAllocator {
+    // 创建一个空的碰撞分配器。`const fn` 使其可以用来初始化 `static`
+    pub const fn new() -> Self {
+        BumpAllocator {
+            heap_start: 0,
+            heap_end: 0,
+            next: 0,
+            allocations: 0,
+        }
+    }
+
+    // 用给定的堆区域初始化分配器
+    /// # Safety
+    ///
+    /// 调用者必须保证该内存区域有效且未被使用，并且只调用一次
+    pub unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        self.heap_start = heap_start;
+        self.heap_end = heap_start + heap_size;
+        self.next = heap_start;
+    }
+}
+
+impl Default for BumpAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl GlobalAlloc for Locked<BumpAllocator> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        interrupts::without_interrupts(|| {
+            let mut bump = self.lock();
+
+            let alloc_start = align_up(bump.next, layout.align());
+            // 使用 `checked_add` 防止超大的分配请求导致整数溢出
+            let alloc_end = match alloc_start.checked_add(layout.size()) {
+                Some(end) => end,
+                None => return ptr::null_mut(),
+            };
+
+            if alloc_end > bump.heap_end {
+                // 内存不足
+                ptr::null_mut()
+            } else {
+                bump.next = alloc_end;
+                bump.allocations += 1;
+                alloc_start as *mut u8
+            }
+        })
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
+        interrupts::without_interrupts(|| {
+            let mut bump = self.lock();
+
+            bump.allocations -= 1;
+            // 所有分配都已释放，整个堆可以重新使用
+            if bump.allocations == 0 {
+                bump.next = bump.heap_start;
+            }
+        })
+    }
+}
+
+#[test_case]
+fn test_bump_reuses_memory_after_all_freed() {
+    let mut arena = super::TestArena::new();
+    let allocator = Locked::new(BumpAllocator::new());
+    unsafe { allocator.lock().init(arena.start(), arena.size()) };
+
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    unsafe {
+        let a = allocator.alloc(layout);
+        let b = allocator.alloc(layout);
+        assert!(!a.is_null() && !b.is_null());
+        assert_eq!(b as usize, a as usize + 64);
+
+        // 只要还有分配没释放，就不会重用内存
+        allocator.dealloc(a, layout);
+        let c = allocator.alloc(layout);
+        assert_eq!(c as usize, b as usize + 64);
+
+        allocator.dealloc(b, layout);
+        allocator.dealloc(c, layout);
+        // 全部释放后从头开始分配
+        assert_eq!(allocator.alloc(layout), a);
+    }
+}
+
+#[test_case]
+fn test_bump_out_of_memory() {
+    let mut arena = super::TestArena::new();
+    let allocator = Locked::new(BumpAllocator::new());
+    unsafe { allocator.lock().init(arena.start(), arena.size()) };
+
+    let layout = Layout::from_size_align(arena.size() + 1, 8).unwrap();
+    assert!(unsafe { allocator.alloc(layout) }.is_null());
+}
@@ -0,0 +1,144 @@
+// 固定大小块分配器(fixed-size block allocator)
+// 把小于等于2048字节的分配向上取整到 `BLOCK_SIZES` 中的某个块大小，每种块大小维护一个空闲链表，分配和释放都只需要弹出/压入链表头，是O(1)的。
+// 链表为空或请求超过最大块大小时，交给后备的链表分配器处理。块释放后放回对应的链表而不是还给后备分配器，所以后备分配器的碎片不会增加
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem;
+use x86_64::instructions::interrupts;
+
+use super::linked_list::LinkedListAllocator;
+use super::Locked;
+
+// 空闲块的头部，只需要指向下一个空闲块
+struct ListNode {
+    next: Option<&'static mut ListNode>,
+}
+
+// 可用的块大小
+// 块大小同时作为块的对齐，所以必须是2的幂；最小为8，保证能放下一个 `ListNode`
+const BLOCK_SIZES: &[usize] = &[8, 16, 32, 64, 128, 256, 512, 1024, 2048];
+
+pub struct FixedSizeBlockAllocator {
+    // 每种块大小对应的空闲链表头
+    list_heads: [Option<&'static mut ListNode>; BLOCK_SIZES.len()],
+    fallback_allocator: LinkedListAllocator,
+}
+
+// 为列表中的每个块大小选择合适的下标
+// 返回第一个能同时满足大小和对齐要求的块大小的下标，超过最大块大小时返回 `None`
+fn list_index(layout: &Layout) -> Option<usize> {
+    let required_block_size = layout.size().max(layout.align());
+    BLOCK_SIZES.iter().position(|&s| s >= required_block_size)
+}
+
+impl FixedSizeBlockAllocator {
+    // 创建一个空的固定大小块分配器
+    pub const fn new() -> Self {
+        // `Option<&mut ListNode>` 没有实现 `Copy`，数组重复表达式需要一个常量
+        const EMPTY: Option<&'static mut ListNode> = None;
+        FixedSizeBlockAllocator {
+            list_heads: [EMPTY; BLOCK_SIZES.len()],
+            fallback_allocator: LinkedListAllocator::new(),
+        }
+    }
+
+    // 用给定的堆区域初始化分配器。所有内存一开始都交给后备分配器，块在第一次分配时才从后备分配器中切出来
+    /// # Safety
+    ///
+    /// 调用者必须保证该内存区域有效且未被使用，并且只调用一次
+    pub unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        self.fallback_allocator.init(heap_start, heap_size);
+    }
+
+    // 使用后备分配器分配
+    fn fallback_alloc(&mut self, layout: Layout) -> *mut u8 {
+        unsafe { self.fallback_allocator.allocate(layout) }
+    }
+}
+
+impl Default for FixedSizeBlockAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        interrupts::without_interrupts(|| {
+            let mut allocator = self.lock();
+            match list_index(&layout) {
+                Some(index) => {
+                    match allocator.list_heads[index].take() {
+                        Some(node) => {
+                            allocator.list_heads[index] = node.next.take();
+                            node as *mut ListNode as *mut u8
+                        }
+                        None => {
+                            // 链表中没有空闲块，从后备分配器分配一个新块
+                            let block_size = BLOCK_SIZES[index];
+                            let block_align = block_size;
+                            let layout = Layout::from_size_align(block_size, block_align).unwrap();
+                            allocator.fallback_alloc(layout)
+                        }
+                    }
+                }
+                None => allocator.fallback_alloc(layout),
+            }
+        })
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        interrupts::without_interrupts(|| {
+            let mut allocator = self.lock();
+            match list_index(&layout) {
+                Some(index) => {
+                    // 把释放的块压入对应链表的头部
+                    let new_node = ListNode {
+                        next: allocator.list_heads[index].take(),
+                    };
+                    // 确认块的大小和对齐足以存放 `ListNode`
+                    assert!(mem::size_of::<ListNode>() <= BLOCK_SIZES[index]);
+                    assert!(mem::align_of::<ListNode>() <= BLOCK_SIZES[index]);
+                    let new_node_ptr = ptr as *mut ListNode;
+                    new_node_ptr.write(new_node);
+                    allocator.list_heads[index] = Some(&mut *new_node_ptr);
+                }
+                None => allocator.fallback_allocator.deallocate(ptr, layout),
+            }
+        })
+    }
+}
+
+#[test_case]
+fn test_list_index() {
+    assert_eq!(list_index(&Layout::from_size_align(1, 1).unwrap()), Some(0));
+    assert_eq!(list_index(&Layout::from_size_align(9, 8).unwrap()), Some(1));
+    // 对齐要求大于大小时按对齐选择块
+    assert_eq!(list_index(&Layout::from_size_align(8, 64).unwrap()), Some(3));
+    assert_eq!(list_index(&Layout::from_size_align(2048, 8).unwrap()), Some(8));
+    assert_eq!(list_index(&Layout::from_size_align(2049, 8).unwrap()), None);
+}
+
+#[test_case]
+fn test_fixed_size_block_reuses_blocks() {
+    let mut arena = super::TestArena::new();
+    let allocator = Locked::new(FixedSizeBlockAllocator::new());
+    unsafe { allocator.lock().init(arena.start(), arena.size()) };
+
+    let small = Layout::from_size_align(24, 8).unwrap();
+    let large = Layout::from_size_align(3000, 8).unwrap();
+    unsafe {
+        let a = allocator.alloc(small);
+        assert!(!a.is_null());
+        allocator.dealloc(a, small);
+        // 同一块大小的分配直接从链表中取回刚释放的块
+        assert_eq!(allocator.alloc(small), a);
+
+        // 超过最大块大小的分配走后备分配器
+        let b = allocator.alloc(large);
+        assert!(!b.is_null());
+        allocator.dealloc(b, large);
+        assert_eq!(allocator.alloc(large), b);
+        assert!(allocator.alloc(large).is_null());
+    }
+}
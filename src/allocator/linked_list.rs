@@ -0,0 +1,248 @@
+// 链表分配器(linked list allocator)
+// 把空闲内存区域本身当作链表节点：每个空闲区域的开头存放该区域的大小和指向下一个空闲区域的指针，所以不需要额外的内存来记录空闲信息。
+// 链表按地址从低到高排序，释放时检查前后相邻的空闲区域并合并，避免堆被切成越来越小、无法满足大分配的碎片
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem;
+use core::ptr;
+use x86_64::instructions::interrupts;
+
+use super::{align_up, Locked};
+
+// 空闲区域的头部
+struct ListNode {
+    size: usize,
+    next: Option<&'static mut ListNode>,
+}
+
+impl ListNode {
+    const fn new(size: usize) -> Self {
+        ListNode { size, next: None }
+    }
+
+    fn start_addr(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    fn end_addr(&self) -> usize {
+        self.start_addr() + self.size
+    }
+}
+
+pub struct LinkedListAllocator {
+    // 哨兵节点，大小为0，`head.next` 指向地址最低的空闲区域
+    head: ListNode,
+}
+
+impl LinkedListAllocator {
+    // 创建一个空的链表分配器
+    pub const fn new() -> Self {
+        Self {
+            head: ListNode::new(0),
+        }
+    }
+
+    // 用给定的堆区域初始化分配器
+    /// # Safety
+    ///
+    /// 调用者必须保证该内存区域有效且未被使用，并且只调用一次
+    pub unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        self.add_free_region(heap_start, heap_size);
+    }
+
+    // 把 `[addr, addr + size)` 按地址顺序插入空闲链表，并与前后相邻的空闲区域合并
+    unsafe fn add_free_region(&mut self, addr: usize, size: usize) {
+        // 该区域必须能放下一个 `ListNode`
+        assert_eq!(align_up(addr, mem::align_of::<ListNode>()), addr);
+        assert!(size >= mem::size_of::<ListNode>());
+
+        // 找到插入位置：`prev` 是最后一个起始地址小于 `addr` 的节点(或哨兵)
+        let head: *mut ListNode = &mut self.head;
+        let mut prev = head;
+        while let Some(ref mut next) = (*prev).next {
+            if next.start_addr() > addr {
+                break;
+            }
+            prev = &mut **next;
+        }
+
+        // 在空闲区域的开头写入新节点，并接上原来的后继
+        let node_ptr = addr as *mut ListNode;
+        node_ptr.write(ListNode::new(size));
+        let node = &mut *node_ptr;
+        node.next = (*prev).next.take();
+
+        // 与后一个空闲区域相邻时合并
+        if let Some(next) = node.next.take() {
+            if node.end_addr() == next.start_addr() {
+                node.size += next.size;
+                node.next = next.next.take();
+            } else {
+                node.next = Some(next);
+            }
+        }
+
+        // 与前一个空闲区域相邻时合并，否则把新节点链入
+        if prev != head && (*prev).end_addr() == addr {
+            (*prev).size += node.size;
+            (*prev).next = node.next.take();
+        } else {
+            (*prev).next = Some(node);
+        }
+    }
+
+    // 查找能满足给定大小和对齐要求的第一个空闲区域(首次适配)，并将其从链表中移除
+    // 返回该区域节点和分配的起始地址
+    fn find_region(&mut self, size: usize, align: usize) -> Option<(&'static mut ListNode, usize)> {
+        let mut current = &mut self.head;
+        while let Some(ref mut region) = current.next {
+            if let Ok(alloc_start) = Self::alloc_from_region(region, size, align) {
+                // 该区域可以满足分配，从链表中移除
+                let next = region.next.take();
+                let ret = Some((current.next.take().unwrap(), alloc_start));
+                current.next = next;
+                return ret;
+            } else {
+                current = current.next.as_mut().unwrap();
+            }
+        }
+        // 没有合适的区域
+        None
+    }
+
+    // 尝试在 `region` 中分配，返回分配的起始地址
+    // 对齐产生的前部空隙和分配之后剩余的尾部空间会重新放回链表，所以它们要么为0，要么至少能放下一个 `ListNode`
+    fn alloc_from_region(region: &ListNode, size: usize, align: usize) -> Result<usize, ()> {
+        let mut alloc_start = align_up(region.start_addr(), align);
+        let front_padding = alloc_start - region.start_addr();
+        if front_padding > 0 && front_padding < mem::size_of::<ListNode>() {
+            // 前部空隙太小，无法作为空闲区域放回链表，跳过一个节点大小后重新对齐
+            alloc_start = align_up(region.start_addr() + mem::size_of::<ListNode>(), align);
+        }
+        let alloc_end = alloc_start.checked_add(size).ok_or(())?;
+
+        if alloc_end > region.end_addr() {
+            // 区域太小
+            return Err(());
+        }
+
+        let excess_size = region.end_addr() - alloc_end;
+        if excess_size > 0 && excess_size < mem::size_of::<ListNode>() {
+            // 剩余空间太小，无法放下一个 `ListNode`
+            return Err(());
+        }
+
+        Ok(alloc_start)
+    }
+
+    // 调整布局，使分配的内存块能在释放时存放 `ListNode`
+    fn size_align(layout: Layout) -> (usize, usize) {
+        let layout = layout
+            .align_to(mem::align_of::<ListNode>())
+            .expect("adjusting alignment failed")
+            .pad_to_align();
+        let size = layout.size().max(mem::size_of::<ListNode>());
+        (size, layout.align())
+    }
+
+    // 分配内存，失败时返回空指针
+    // 作为固定大小块分配器的后备分配器时直接调用，不经过 `Locked` 的锁
+    /// # Safety
+    ///
+    /// 分配器必须已经用 `init` 初始化
+    pub unsafe fn allocate(&mut self, layout: Layout) -> *mut u8 {
+        let (size, align) = Self::size_align(layout);
+
+        if let Some((region, alloc_start)) = self.find_region(size, align) {
+            let region_start = region.start_addr();
+            let region_end = region.end_addr();
+            let alloc_end = alloc_start + size;
+            // 把前部空隙和尾部剩余空间放回链表
+            if alloc_start > region_start {
+                self.add_free_region(region_start, alloc_start - region_start);
+            }
+            if region_end > alloc_end {
+                self.add_free_region(alloc_end, region_end - alloc_end);
+            }
+            alloc_start as *mut u8
+        } else {
+            ptr::null_mut()
+        }
+    }
+
+    // 释放由 `allocate` 分配的内存
+    /// # Safety
+    ///
+    /// `ptr` 必须是这个分配器的 `allocate` 用同样的 `layout` 返回的、尚未释放的指针
+    pub unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout) {
+        let (size, _) = Self::size_align(layout);
+        self.add_free_region(ptr as usize, size)
+    }
+}
+
+impl Default for LinkedListAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl GlobalAlloc for Locked<LinkedListAllocator> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        interrupts::without_interrupts(|| {
+            self.lock().allocate(layout)
+        })
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        interrupts::without_interrupts(|| {
+            self.lock().deallocate(ptr, layout)
+        })
+    }
+}
+
+#[test_case]
+fn test_linked_list_reuses_freed_region() {
+    let mut arena = super::TestArena::new();
+    let allocator = Locked::new(LinkedListAllocator::new());
+    unsafe { allocator.lock().init(arena.start(), arena.size()) };
+
+    let layout = Layout::from_size_align(128, 8).unwrap();
+    unsafe {
+        let a = allocator.alloc(layout);
+        let b = allocator.alloc(layout);
+        assert!(!a.is_null() && !b.is_null());
+        allocator.dealloc(a, layout);
+        // 首次适配会重新使用刚释放的低地址区域
+        assert_eq!(allocator.alloc(layout), a);
+        allocator.dealloc(a, layout);
+        allocator.dealloc(b, layout);
+    }
+}
+
+#[test_case]
+fn test_linked_list_merges_adjacent_regions() {
+    let mut arena = super::TestArena::new();
+    let size = arena.size();
+    let allocator = Locked::new(LinkedListAllocator::new());
+    unsafe { allocator.lock().init(arena.start(), size) };
+
+    // 把整个区域切成4块后按乱序释放，合并后应能再次分配一整块
+    let quarter = Layout::from_size_align(size / 4, 8).unwrap();
+    let whole = Layout::from_size_align(size, 8).unwrap();
+    unsafe {
+        let blocks = [
+            allocator.alloc(quarter),
+            allocator.alloc(quarter),
+            allocator.alloc(quarter),
+            allocator.alloc(quarter),
+        ];
+        assert!(blocks.iter().all(|b| !b.is_null()));
+        assert!(allocator.alloc(quarter).is_null());
+
+        for &i in &[1, 3, 0, 2] {
+            allocator.dealloc(blocks[i], quarter);
+        }
+        let all = allocator.alloc(whole);
+        assert_eq!(all as usize, arena.start());
+    }
+}
@@ -0,0 +1,126 @@
+// 内核堆
+// 在虚拟地址空间中划出一块固定区域作为内核堆，为其中的每一页分配物理帧并建立映射，然后交给全局分配器管理。
+// 注册了 `#[global_allocator]` 之后，`alloc` crate 中的 `Box`、`Vec`、`String`、`BTreeMap`、`Rc` 等类型就可以在内核中使用了
+//
+// 全局分配器的实现通过cargo特性选择，三者只能开启一个：
+// - `alloc-bump`: 碰撞分配器，只向前移动指针，全部释放后才能重用内存。速度最快，但碎片最严重
+// - `alloc-linked-list`: 链表分配器，按地址排序的空闲链表，释放时与相邻空闲区域合并
+// - `alloc-fixed-size-block`(默认): 固定大小块分配器，小对象按块大小分类管理，大对象交给链表分配器
+
+use core::alloc::Layout;
+use spin::{Mutex, MutexGuard};
+use x86_64::structures::paging::mapper::MapToError;
+use x86_64::structures::paging::{PageTableFlags, Size4KiB};
+use x86_64::VirtAddr;
+
+use crate::memory::paging;
+
+pub mod bump;
+pub mod fixed_size_block;
+pub mod linked_list;
+
+#[cfg(any(
+    all(feature = "alloc-bump", feature = "alloc-linked-list"),
+    all(feature = "alloc-bump", feature = "alloc-fixed-size-block"),
+    all(feature = "alloc-linked-list", feature = "alloc-fixed-size-block"),
+))]
+compile_error!("only one of the `alloc-bump`, `alloc-linked-list` and `alloc-fixed-size-block` features can be enabled");
+
+#[cfg(not(any(
+    feature = "alloc-bump",
+    feature = "alloc-linked-list",
+    feature = "alloc-fixed-size-block",
+)))]
+compile_error!("one of the `alloc-bump`, `alloc-linked-list` and `alloc-fixed-size-block` features must be enabled");
+
+// 根据开启的特性选择全局分配器的实现
+#[cfg(feature = "alloc-bump")]
+type KernelAllocator = bump::BumpAllocator;
+#[cfg(feature = "alloc-linked-list")]
+type KernelAllocator = linked_list::LinkedListAllocator;
+#[cfg(feature = "alloc-fixed-size-block")]
+type KernelAllocator = fixed_size_block::FixedSizeBlockAllocator;
+
+// 堆的起始虚拟地址。选择一个容易辨认、且不会与内核、bootloader和物理内存直接映射区域重叠的地址
+pub const HEAP_START: usize = 0x_4444_4444_0000;
+// 堆的大小(4 MiB)，其中一大部分留给控制台的回滚缓冲区，预算见 `vga_buffer` 中的 `SCROLLBACK_LINES`
+pub const HEAP_SIZE: usize = 4 * 1024 * 1024;
+
+// 注册全局分配器。`alloc` crate 中所有的堆分配最终都会调用它
+#[global_allocator]
+static ALLOCATOR: Locked<KernelAllocator> = Locked::new(KernelAllocator::new());
+
+// 映射堆区域并初始化全局分配器
+// 需要在 `memory::init` 之后、第一次使用堆之前调用一次
+pub fn init_heap() -> Result<(), MapToError<Size4KiB>> {
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+    paging::map_range(VirtAddr::new(HEAP_START as u64), HEAP_SIZE as u64, flags)?;
+
+    // 堆区域刚刚映射完成且没有被其他地方使用，所以可以安全地交给分配器
+    unsafe {
+        ALLOCATOR.lock().init(HEAP_START, HEAP_SIZE);
+    }
+
+    Ok(())
+}
+
+// 堆内存耗尽或请求的布局无法满足时调用
+// 内核中无法恢复这种错误，直接panic并打印出请求的大小和对齐
+#[alloc_error_handler]
+fn alloc_error_handler(layout: Layout) -> ! {
+    panic!("allocation error: {:?}", layout)
+}
+
+// 带自旋锁的包装类型
+// `GlobalAlloc` 的方法只接收 `&self`，而分配器需要修改内部状态。Rust的孤儿规则不允许为 `spin::Mutex<A>` 实现外部trait，
+// 所以用这个本地类型包装一层，再为 `Locked<各分配器>` 实现 `GlobalAlloc`
+// 各 `GlobalAlloc` 实现在关闭中断的情况下持有锁：否则线程持锁时被定时器抢占，另一个线程在关闭中断时分配内存(如调度器内部)会永远自旋
+pub struct Locked<A> {
+    inner: Mutex<A>,
+}
+
+impl<A> Locked<A> {
+    pub const fn new(inner: A) -> Self {
+        Locked {
+            inner: Mutex::new(inner),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, A> {
+        self.inner.lock()
+    }
+}
+
+// 把地址 `addr` 向上对齐到 `align`
+// `align` 必须是2的幂，这样 `align - 1` 的二进制全是低位的1，清除这些位即可向下对齐，先加上 `align - 1` 则变成向上对齐
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+
+// 测试用的堆区域，在栈上提供一小块对齐的内存，让每种分配器的测试都不依赖当前选择的全局分配器
+#[cfg(test)]
+#[repr(C, align(4096))]
+struct TestArena([u8; 4096]);
+
+#[cfg(test)]
+impl TestArena {
+    fn new() -> Self {
+        TestArena([0; 4096])
+    }
+
+    fn start(&mut self) -> usize {
+        self.0.as_mut_ptr() as usize
+    }
+
+    fn size(&self) -> usize {
+        self.0.len()
+    }
+}
+
+#[test_case]
+fn test_align_up() {
+    assert_eq!(align_up(0, 8), 0);
+    assert_eq!(align_up(1, 8), 8);
+    assert_eq!(align_up(8, 8), 8);
+    assert_eq!(align_up(0x1001, 0x1000), 0x2000);
+}
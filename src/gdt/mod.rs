@@ -74,6 +74,21 @@ pub fn init() {
     }
 }
 
+#[test_case]
+fn test_double_fault_stack_configured() {
+    // 双重异常栈必须已经设置，否则栈溢出时CPU会在无效的栈上压入异常帧从而触发三重异常
+    let stack_end = TSS.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize];
+    assert_ne!(stack_end.as_u64(), 0);
+}
+
+#[test_case]
+fn test_code_selector_loaded() {
+    use x86_64::instructions::segmentation::CS;
+
+    // `init` 之后CS寄存器应指向GDT中的内核代码段
+    assert_eq!(CS::get_reg(), GDT.1.code_selector);
+}
+
 // `CS::set_reg(GDT.1.code_selector);` 这行代码本身并不直接实现从保护模式到长模式的转换，也就是说它不切换CPU运作状态。
 // 在 x86_64 架构中，进入长模式（Long Mode）是一个几步进行的复杂过程。具体来说，需要：
 // 1. 开启分页（Paging），将CR0寄存器的分页位置1。
@@ -0,0 +1,99 @@
+// IO APIC
+// 把外部设备的中断线(全局系统中断，GSI)转发给某个CPU的本地APIC。每条中断线对应一个64位的重定向项，
+// 其中指定了向量号、目标APIC ID、触发方式、极性以及是否屏蔽。
+// 寄存器通过间接方式访问：先把寄存器编号写入IOREGSEL，再读写IOWIN
+
+use x86_64::VirtAddr;
+
+const IOREGSEL: usize = 0x00;
+const IOWIN: usize = 0x10;
+// 版本寄存器，第16-23位是最大的重定向项下标
+const REG_VERSION: u32 = 0x01;
+// 第一个重定向项的低32位，每项占两个寄存器
+const REG_REDIRECTION_BASE: u32 = 0x10;
+
+// 重定向项中的标志位
+const ACTIVE_LOW: u64 = 1 << 13;
+const LEVEL_TRIGGERED: u64 = 1 << 15;
+const MASKED: u64 = 1 << 16;
+
+pub struct IoApic {
+    base: VirtAddr,
+    gsi_base: u32,
+    entries: u32,
+}
+
+impl IoApic {
+    /// # Safety
+    ///
+    /// 调用者必须保证 `base` 处映射了IO APIC的寄存器(关闭缓存)
+    pub unsafe fn new(base: VirtAddr, gsi_base: u32) -> Self {
+        let mut io_apic = IoApic {
+            base,
+            gsi_base,
+            entries: 0,
+        };
+        io_apic.entries = ((io_apic.read(REG_VERSION) >> 16) & 0xff) + 1;
+        io_apic
+    }
+
+    fn read(&mut self, reg: u32) -> u32 {
+        unsafe {
+            (self.base + IOREGSEL).as_mut_ptr::<u32>().write_volatile(reg);
+            (self.base + IOWIN).as_ptr::<u32>().read_volatile()
+        }
+    }
+
+    fn write(&mut self, reg: u32, value: u32) {
+        unsafe {
+            (self.base + IOREGSEL).as_mut_ptr::<u32>().write_volatile(reg);
+            (self.base + IOWIN).as_mut_ptr::<u32>().write_volatile(value);
+        }
+    }
+
+    // 该IO APIC是否负责全局系统中断 `gsi`
+    pub fn handles(&self, gsi: u32) -> bool {
+        gsi >= self.gsi_base && gsi < self.gsi_base + self.entries
+    }
+
+    // 写入 `gsi` 对应的重定向项
+    pub fn set_entry(&mut self, gsi: u32, entry: u64) {
+        let reg = REG_REDIRECTION_BASE + (gsi - self.gsi_base) * 2;
+        // 先写高32位(目标)，再写低32位(向量和屏蔽位)，避免中间状态下把中断发给错误的CPU
+        self.write(reg + 1, (entry >> 32) as u32);
+        self.write(reg, entry as u32);
+    }
+
+    // 屏蔽所有中断线，固件可能留下了不需要的设置
+    pub fn mask_all(&mut self) {
+        for i in 0..self.entries {
+            self.set_entry(self.gsi_base + i, MASKED);
+        }
+    }
+}
+
+// 构造一个重定向项：以固定模式、物理目标模式把中断 `vector` 发送给APIC ID为 `destination` 的CPU
+// `flags` 是MADT中断源重定向中的MPS INTI标志，0表示使用ISA总线的默认设置(高电平有效、边沿触发)
+pub fn redirection_entry(vector: u8, destination: u8, flags: u16, masked: bool) -> u64 {
+    let mut entry = u64::from(vector) | (u64::from(destination) << 56);
+    // 极性：0b11表示低电平有效
+    if flags & 0b11 == 0b11 {
+        entry |= ACTIVE_LOW;
+    }
+    // 触发方式：0b11表示电平触发
+    if (flags >> 2) & 0b11 == 0b11 {
+        entry |= LEVEL_TRIGGERED;
+    }
+    if masked {
+        entry |= MASKED;
+    }
+    entry
+}
+
+#[test_case]
+fn test_redirection_entry() {
+    assert_eq!(redirection_entry(33, 0, 0, false), 33);
+    assert_eq!(redirection_entry(32, 1, 0, true), 32 | MASKED | (1 << 56));
+    // 低电平有效、电平触发，例如ACPI的SCI
+    assert_eq!(redirection_entry(40, 0, 0b1111, false), 40 | ACTIVE_LOW | LEVEL_TRIGGERED);
+}
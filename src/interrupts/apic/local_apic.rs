@@ -0,0 +1,93 @@
+// 本地APIC
+// 每个CPU核心都有一个本地APIC，负责接收IO APIC转发来的中断、发送EOI，并提供一个可以周期触发的定时器。
+// 它的寄存器通过4KiB的MMIO区域访问，每个寄存器是按16字节对齐的32位值
+
+use x86_64::registers::model_specific::Msr;
+use x86_64::VirtAddr;
+
+use crate::time::pit;
+
+// IA32_APIC_BASE模型相关寄存器，第11位是全局启用位
+const IA32_APIC_BASE_MSR: u32 = 0x1b;
+const APIC_GLOBAL_ENABLE: u64 = 1 << 11;
+
+// 寄存器偏移
+const REG_ID: usize = 0x20;
+const REG_TASK_PRIORITY: usize = 0x80;
+const REG_EOI: usize = 0xb0;
+const REG_SPURIOUS: usize = 0xf0;
+const REG_LVT_TIMER: usize = 0x320;
+const REG_TIMER_INITIAL_COUNT: usize = 0x380;
+const REG_TIMER_CURRENT_COUNT: usize = 0x390;
+const REG_TIMER_DIVIDE: usize = 0x3e0;
+
+// 伪中断向量寄存器中的软件启用位
+const SPURIOUS_APIC_ENABLE: u32 = 1 << 8;
+// LVT中的屏蔽位和定时器的周期模式位
+const LVT_MASKED: u32 = 1 << 16;
+const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+// 定时器时钟16分频
+const TIMER_DIVIDE_BY_16: u32 = 0b0011;
+// 校准定时器时用PIT等待的毫秒数
+const CALIBRATION_MS: u32 = 10;
+
+pub struct LocalApic {
+    base: VirtAddr,
+}
+
+impl LocalApic {
+    /// # Safety
+    ///
+    /// 调用者必须保证 `base` 处映射了本地APIC的寄存器(关闭缓存)
+    pub unsafe fn new(base: VirtAddr) -> Self {
+        LocalApic { base }
+    }
+
+    fn read(&self, reg: usize) -> u32 {
+        unsafe { (self.base + reg).as_ptr::<u32>().read_volatile() }
+    }
+
+    fn write(&self, reg: usize, value: u32) {
+        unsafe { (self.base + reg).as_mut_ptr::<u32>().write_volatile(value) }
+    }
+
+    // 开启本地APIC：设置全局启用位和软件启用位，并把伪中断送到 `spurious_vector`
+    pub fn enable(&self, spurious_vector: u8) {
+        let mut msr = Msr::new(IA32_APIC_BASE_MSR);
+        unsafe {
+            let value = msr.read();
+            msr.write(value | APIC_GLOBAL_ENABLE);
+        }
+        self.write(REG_SPURIOUS, SPURIOUS_APIC_ENABLE | u32::from(spurious_vector));
+        // 任务优先级为0，接收所有中断
+        self.write(REG_TASK_PRIORITY, 0);
+    }
+
+    // 本地APIC的ID，IO APIC按它把中断发送到这个CPU
+    pub fn id(&self) -> u8 {
+        (self.read(REG_ID) >> 24) as u8
+    }
+
+    // 发送EOI，写入任意值即可
+    pub fn end_of_interrupt(&self) {
+        self.write(REG_EOI, 0);
+    }
+
+    // 用PIT校准定时器后，让它以接近 `hz` 的频率周期性地触发 `vector` 中断，返回实际的周期(纳秒)
+    // 校准期间忙等待约10毫秒，需要在关闭中断时调用
+    pub fn start_periodic_timer(&self, vector: u8, hz: u32) -> u64 {
+        self.write(REG_TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+        // 屏蔽的单次模式下从最大值开始倒数，等待一段已知的时间后看数了多少
+        self.write(REG_LVT_TIMER, LVT_MASKED | u32::from(vector));
+        self.write(REG_TIMER_INITIAL_COUNT, u32::MAX);
+        pit::spin_wait_ms(CALIBRATION_MS);
+        let elapsed = u64::from(u32::MAX - self.read(REG_TIMER_CURRENT_COUNT));
+
+        let counts_per_second = elapsed * 1000 / u64::from(CALIBRATION_MS);
+        let initial_count = (counts_per_second / u64::from(hz.max(1))).clamp(1, u64::from(u32::MAX));
+        self.write(REG_LVT_TIMER, LVT_TIMER_PERIODIC | u32::from(vector));
+        self.write(REG_TIMER_INITIAL_COUNT, initial_count as u32);
+
+        initial_count * 1_000_000_000 / counts_per_second.max(1)
+    }
+}
@@ -0,0 +1,158 @@
+// APIC中断控制器
+// 8259 PIC只能连接15条中断线，并且只能把中断送到一个CPU。现代x86平台使用APIC：每个CPU有一个本地APIC，
+// 设备的中断由IO APIC转发给指定CPU的本地APIC。启动时仍然使用PIC，在内存管理和堆初始化之后调用 `init` 切换到APIC：
+// 1. 通过CPUID确认CPU支持本地APIC；
+// 2. 从ACPI的MADT(见 `acpi` 模块)中找到本地APIC和IO APIC的地址，以及ISA中断的重定向；
+// 3. 开启本地APIC，把已注册的IRQ(键盘、定时器等)经由IO APIC路由到与PIC模式相同的向量号，然后屏蔽8259 PIC。
+// 定时器中断可以继续来自PIT(经IO APIC转发)，也可以改用本地APIC自带的定时器
+
+use alloc::vec::Vec;
+use core::arch::x86_64::__cpuid;
+use core::sync::atomic::Ordering;
+use spin::{Mutex, Once};
+use x86_64::instructions::interrupts;
+use x86_64::structures::paging::mapper::MapToError;
+use x86_64::structures::paging::Size4KiB;
+use x86_64::PhysAddr;
+
+use super::{irq, pics, InterruptIndex};
+use crate::acpi::{self, AcpiError};
+use crate::memory::paging;
+use crate::time;
+use io_apic::IoApic;
+use local_apic::LocalApic;
+
+pub mod io_apic;
+pub mod local_apic;
+
+// 本地APIC伪中断使用的向量号。低4位必须全为1，使用最后一个向量
+pub const SPURIOUS_VECTOR: u8 = 0xff;
+
+// APIC寄存器区域的大小
+const APIC_MMIO_SIZE: u64 = 4096;
+
+// 切换到APIC失败的原因，失败时继续使用8259 PIC
+#[derive(Debug)]
+pub enum ApicError {
+    // CPU不支持本地APIC
+    Unsupported,
+    // 无法读取ACPI表
+    Acpi(AcpiError),
+    // ACPI表中没有MADT
+    MadtNotFound,
+    // MADT中没有IO APIC
+    NoIoApic,
+    // 映射APIC寄存器失败
+    Map(MapToError<Size4KiB>),
+}
+
+impl From<MapToError<Size4KiB>> for ApicError {
+    fn from(err: MapToError<Size4KiB>) -> Self {
+        ApicError::Map(err)
+    }
+}
+
+// 定时器中断的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerSource {
+    // 继续使用PIT，IRQ0经IO APIC转发
+    Pit,
+    // 使用本地APIC定时器，以 `time::TIMER_FREQUENCY_HZ` 的频率周期触发，PIT的中断被屏蔽
+    LocalApic,
+}
+
+static LOCAL_APIC: Once<LocalApic> = Once::new();
+static IO_APICS: Mutex<Vec<IoApic>> = Mutex::new(Vec::new());
+
+// CPU是否有本地APIC：CPUID功能号1返回的EDX第9位
+pub fn is_supported() -> bool {
+    // 较新的工具链中 `__cpuid` 已经不再是unsafe的
+    #[allow(unused_unsafe)]
+    let result = unsafe { __cpuid(1) };
+    result.edx & (1 << 9) != 0
+}
+
+// 从8259 PIC切换到APIC
+// 需要在 `memory::init` 和 `allocator::init_heap` 之后调用。重复调用时直接返回
+pub fn init(timer: TimerSource) -> Result<(), ApicError> {
+    if super::controller() == super::Controller::Apic {
+        return Ok(());
+    }
+    if !is_supported() {
+        return Err(ApicError::Unsupported);
+    }
+    let acpi = acpi::init().map_err(ApicError::Acpi)?;
+    let madt = acpi.madt.as_ref().ok_or(ApicError::MadtNotFound)?;
+    if madt.io_apics.is_empty() {
+        return Err(ApicError::NoIoApic);
+    }
+
+    // APIC的寄存器位于物理内存之外，需要单独映射
+    let local_base = unsafe { paging::map_mmio(PhysAddr::new(madt.local_apic_address), APIC_MMIO_SIZE)? };
+    let mut io_apics = Vec::with_capacity(madt.io_apics.len());
+    for entry in &madt.io_apics {
+        let base = unsafe { paging::map_mmio(PhysAddr::new(u64::from(entry.address)), APIC_MMIO_SIZE)? };
+        let mut io_apic = unsafe { IoApic::new(base, entry.gsi_base) };
+        io_apic.mask_all();
+        io_apics.push(io_apic);
+    }
+
+    interrupts::without_interrupts(|| {
+        let local = LOCAL_APIC.call_once(|| unsafe { LocalApic::new(local_base) });
+        local.enable(SPURIOUS_VECTOR);
+        *IO_APICS.lock() = io_apics;
+
+        // 重新路由所有已经注册了处理函数的IRQ，定时器按所选的来源单独处理
+        for irq in irq::registered_irqs().filter(|&irq| irq != InterruptIndex::Timer.irq()) {
+            route_irq(irq, irq::vector(irq));
+        }
+        match timer {
+            TimerSource::Pit => route_irq(InterruptIndex::Timer.irq(), InterruptIndex::Timer.as_u8()),
+            TimerSource::LocalApic => {
+                let period = local.start_periodic_timer(InterruptIndex::Timer.as_u8(), time::TIMER_FREQUENCY_HZ);
+                time::set_tick_period(period);
+            }
+        }
+
+        // 屏蔽8259 PIC的所有中断线，此后外部中断只通过IO APIC到达
+        unsafe { pics::PICS.lock().disable() };
+        super::APIC_ACTIVE.store(true, Ordering::Release);
+    });
+    Ok(())
+}
+
+// 向本地APIC发送EOI
+pub fn end_of_interrupt() {
+    if let Some(local) = LOCAL_APIC.get() {
+        local.end_of_interrupt();
+    }
+}
+
+// ISA中断 `irq` 对应的全局系统中断号和MPS INTI标志，MADT中没有重定向时两者相同
+fn isa_irq_to_gsi(irq: u8) -> (u32, u16) {
+    acpi::tables()
+        .and_then(|acpi| acpi.madt.as_ref())
+        .and_then(|madt| madt.overrides.iter().find(|entry| entry.source == irq))
+        .map_or((u32::from(irq), 0), |entry| (entry.gsi, entry.flags))
+}
+
+// 把ISA中断 `irq` 经由IO APIC路由到当前CPU的 `vector` 向量
+pub fn route_irq(irq: u8, vector: u8) {
+    set_isa_entry(irq, vector, false);
+}
+
+// 屏蔽ISA中断 `irq`
+pub fn mask_irq(irq: u8) {
+    set_isa_entry(irq, pics::PIC_1_OFFSET + irq, true);
+}
+
+fn set_isa_entry(irq: u8, vector: u8, masked: bool) {
+    let destination = LOCAL_APIC.get().expect("local APIC not initialized").id();
+    let (gsi, flags) = isa_irq_to_gsi(irq);
+    let entry = io_apic::redirection_entry(vector, destination, flags, masked);
+    interrupts::without_interrupts(|| {
+        if let Some(io_apic) = IO_APICS.lock().iter_mut().find(|io_apic| io_apic.handles(gsi)) {
+            io_apic.set_entry(gsi, entry);
+        }
+    });
+}
@@ -0,0 +1,183 @@
+// 外部中断(IRQ)的注册和分发
+// 16条ISA中断线固定映射到向量 `PIC_1_OFFSET + irq`，IDT中每个向量都指向同一个分发函数的一份入口，
+// 入口把IRQ号交给 `dispatch`：计数、调用注册的处理函数，最后自动向当前的中断控制器发送EOI。
+// 驱动只需要调用 `register_irq` 注册处理函数，不再需要修改IDT和 `InterruptIndex`。
+// 定时器(IRQ0)的入口还要切换线程，使用 `thread::context` 中的汇编入口，但同样经过 `dispatch`
+//
+// 中断处理函数和线程上下文共用的锁遵循同一条规则：
+// 线程上下文中总是在 `interrupts::without_interrupts` 里加锁，持有锁期间不会被中断打断，
+// 所以中断处理函数中拿到锁时不会有线程卡在临界区中间，可以直接加锁，也可以用 `try_lock` 在锁被占用时跳过。
+// 其他模块中遵循这条规则的锁只注明引用这里
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+use x86_64::structures::idt::{HandlerFunc, InterruptStackFrame};
+
+use super::{apic, controller, pics, Controller, InterruptIndex};
+
+// ISA中断线的数量
+pub const IRQ_COUNT: usize = 16;
+
+// IRQ处理函数。在中断上下文中执行，不能阻塞，EOI由分发函数发送
+pub type IrqHandler = fn();
+
+// 注册或注销失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrqError {
+    // IRQ号超出0..16
+    InvalidIrq,
+    // 这条中断线已经有处理函数
+    AlreadyRegistered,
+    // 这条中断线没有处理函数
+    NotRegistered,
+}
+
+// 各IRQ的处理函数，中断处理函数中直接加锁(见模块开头的加锁规则)
+static HANDLERS: Mutex<[Option<IrqHandler>; IRQ_COUNT]> = Mutex::new([None; IRQ_COUNT]);
+// 各IRQ收到的中断次数，包括没有处理函数的中断
+static COUNTS: [AtomicU64; IRQ_COUNT] = [const { AtomicU64::new(0) }; IRQ_COUNT];
+
+// IRQ对应的中断向量号，PIC和APIC模式下相同
+pub fn vector(irq: u8) -> u8 {
+    pics::PIC_1_OFFSET + irq
+}
+
+// 为 `irq` 注册处理函数，并在当前的中断控制器上打开这条中断线
+pub fn register_irq(irq: u8, handler: IrqHandler) -> Result<(), IrqError> {
+    if usize::from(irq) >= IRQ_COUNT {
+        return Err(IrqError::InvalidIrq);
+    }
+    interrupts::without_interrupts(|| {
+        let mut handlers = HANDLERS.lock();
+        let slot = &mut handlers[usize::from(irq)];
+        if slot.is_some() {
+            return Err(IrqError::AlreadyRegistered);
+        }
+        *slot = Some(handler);
+        Ok(())
+    })?;
+    unmask(irq);
+    Ok(())
+}
+
+// 注销 `irq` 的处理函数并屏蔽这条中断线，返回原来的处理函数
+pub fn unregister_irq(irq: u8) -> Result<IrqHandler, IrqError> {
+    if usize::from(irq) >= IRQ_COUNT {
+        return Err(IrqError::InvalidIrq);
+    }
+    let handler = interrupts::without_interrupts(|| HANDLERS.lock()[usize::from(irq)].take());
+    mask(irq);
+    handler.ok_or(IrqError::NotRegistered)
+}
+
+// `irq` 当前是否注册了处理函数
+pub fn is_registered(irq: u8) -> bool {
+    usize::from(irq) < IRQ_COUNT
+        && interrupts::without_interrupts(|| HANDLERS.lock()[usize::from(irq)].is_some())
+}
+
+// 已注册处理函数的IRQ，`apic::init` 切换控制器时据此重新路由
+pub fn registered_irqs() -> impl Iterator<Item = u8> {
+    let handlers = interrupts::without_interrupts(|| *HANDLERS.lock());
+    (0..IRQ_COUNT as u8).filter(move |&irq| handlers[usize::from(irq)].is_some())
+}
+
+// `irq` 收到的中断次数
+pub fn irq_count(irq: u8) -> u64 {
+    COUNTS.get(usize::from(irq)).map_or(0, |count| count.load(Ordering::Relaxed))
+}
+
+// 在当前的中断控制器上打开中断线
+// APIC模式下定时器向量由 `apic::init` 按所选的定时器来源设置，这里不能把PIT再路由一次
+fn unmask(irq: u8) {
+    match controller() {
+        Controller::Pic => pics::unmask(irq),
+        Controller::Apic if irq == InterruptIndex::Timer.irq() => {}
+        Controller::Apic => apic::route_irq(irq, vector(irq)),
+    }
+}
+
+fn mask(irq: u8) {
+    match controller() {
+        Controller::Pic => pics::mask(irq),
+        Controller::Apic if irq == InterruptIndex::Timer.irq() => {}
+        Controller::Apic => apic::mask_irq(irq),
+    }
+}
+
+// 通知当前的中断控制器 `irq` 已经处理完毕
+fn end_of_interrupt(irq: u8) {
+    match controller() {
+        // 向PIC发送EOI需要锁定 `PICS`，并且错误地发送EOI可能导致中断管理混乱，所以是unsafe的
+        Controller::Pic => unsafe {
+            pics::PICS.lock().notify_end_of_interrupt(vector(irq));
+        },
+        Controller::Apic => apic::end_of_interrupt(),
+    }
+}
+
+// 所有IRQ的公共处理流程：计数，调用处理函数(如果有)，发送EOI
+// 调用处理函数之前先释放锁，处理函数中也可以注册或注销IRQ
+pub(crate) fn dispatch(irq: u8) {
+    COUNTS[usize::from(irq)].fetch_add(1, Ordering::Relaxed);
+    let handler = HANDLERS.lock()[usize::from(irq)];
+    if let Some(handler) = handler {
+        handler();
+    }
+    end_of_interrupt(irq);
+}
+
+// 为每条中断线生成一个入口函数，入口只负责把IRQ号传给 `dispatch`
+macro_rules! irq_entries {
+    ($($irq:literal),*) => {
+        [$({
+            extern "x86-interrupt" fn entry(_stack_frame: InterruptStackFrame) {
+                dispatch($irq);
+            }
+            entry as HandlerFunc
+        }),*]
+    };
+}
+
+// 各IRQ的入口，按IRQ号排列，由 `init_idt` 填入IDT
+pub(super) static ENTRIES: [HandlerFunc; IRQ_COUNT] =
+    irq_entries!(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15);
+
+#[test_case]
+fn test_register_and_dispatch() {
+    use core::sync::atomic::AtomicBool;
+
+    // 选用没有设备的IRQ5，用 `int` 指令模拟它的中断
+    static CALLED: AtomicBool = AtomicBool::new(false);
+    fn handler() {
+        CALLED.store(true, Ordering::Relaxed);
+    }
+    const IRQ: u8 = 5;
+
+    assert_eq!(register_irq(IRQ, handler), Ok(()));
+    assert_eq!(register_irq(IRQ, handler), Err(IrqError::AlreadyRegistered));
+    assert!(is_registered(IRQ));
+    assert!(registered_irqs().any(|irq| irq == IRQ));
+
+    let before = irq_count(IRQ);
+    unsafe { core::arch::asm!("int {}", const pics::PIC_1_OFFSET + IRQ) };
+    assert!(CALLED.swap(false, Ordering::Relaxed));
+    assert_eq!(irq_count(IRQ), before + 1);
+
+    assert!(unregister_irq(IRQ).is_ok());
+    assert_eq!(unregister_irq(IRQ), Err(IrqError::NotRegistered));
+    assert!(!is_registered(IRQ));
+    // 注销后仍然计数，但不再调用处理函数
+    unsafe { core::arch::asm!("int {}", const pics::PIC_1_OFFSET + IRQ) };
+    assert!(!CALLED.load(Ordering::Relaxed));
+    assert_eq!(irq_count(IRQ), before + 2);
+}
+
+#[test_case]
+fn test_invalid_irq() {
+    fn handler() {}
+    assert_eq!(register_irq(IRQ_COUNT as u8, handler), Err(IrqError::InvalidIrq));
+    assert_eq!(unregister_irq(IRQ_COUNT as u8), Err(IrqError::InvalidIrq));
+    assert_eq!(irq_count(IRQ_COUNT as u8), 0);
+}
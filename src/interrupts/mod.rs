@@ -1,33 +1,118 @@
+use core::fmt;
 use lazy_static::lazy_static;
-// 从`pc_keyboard` crate（包）导入 `Keyboard` 结构和 `layouts` 模块。该crate提供了处理PC样式键盘输入的方法和数据结构
-use pc_keyboard::{Keyboard, layouts};
-// 从spin库导入其版本的互斥锁（Mutex）。这种类型的锁特别适合操作系统级应用，因为操作系统不总是可以休眠线程以等待锁释放
-use spin::lock_api::Mutex;
-// 导入用于低级别I/O端口操作的 `Port` 结构体，与硬件设备进行通信时常用到
-use x86_64::instructions::port::Port;
 // 从x86_64标准库中导入关于中断描述符表(Interrupt Descriptor Table, IDT)和中断栈帧(Interrupt Stack Frame) 的结构体定义。IDT用于定义中断服务例程(ISRs)，而中断栈帧保存发生中断时CPU寄存器状态
-use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
+use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, SelectorErrorCode};
 
-// 引入前面定义好的枚举 `InterruptIndex` ，代表各个片段(PICS)相关联映射向量编号概念理解工具项
-use pics::InterruptIndex;
+use core::sync::atomic::{AtomicBool, Ordering};
 
-// 导出当前crate提供的打印函数 "`print!`" 和 "`println!"` 宏，方便其他模块输出信息至控制台或屏幕
-use crate::{print, println};
+// 引入双重异常使用的中断栈索引
+use crate::gdt;
+// 定时器中断时切换线程
+use crate::thread;
 
+// 导出当前crate提供的打印宏 "`println!`"，方便其他模块输出信息至控制台或屏幕
+use crate::println;
+
+pub mod apic;
+pub mod irq;
+pub mod page_fault;
 pub mod pics;
 
+pub use irq::{register_irq, unregister_irq, IrqError, IrqHandler};
+
+// 这里通过派生(`derive`)特性给我们的 `InterruptIndex` 枚举添加调试、克隆和复制功能。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// 同时用 `#[repr(u8)]` 属性确保枚举底层数据类型为 u8
+#[repr(u8)]
+pub enum InterruptIndex {
+    // 定义枚举，其中每一项代表内核自己使用的硬件中断的向量号。首项 'Timer' 从 `PIC_1_OFFSET`(32) 开始，'Keyboard' 自动递增为33
+    // 无论使用8259 PIC还是APIC，同一个设备都使用同一个向量号，所以IDT不需要随中断控制器改变。其他设备的驱动直接使用IRQ号，见 `irq` 模块
+    Timer = pics::PIC_1_OFFSET,
+    Keyboard,
+}
+
+impl InterruptIndex {
+    // 允许你把枚举内项直接转换成相应的u8数字表达
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+    // 转成usize，方便作为IDT的下标
+    pub fn as_usize(self) -> usize {
+        usize::from(self.as_u8())
+    }
+    // 对应的ISA中断请求线(IRQ)编号：定时器为IRQ0，键盘为IRQ1
+    pub fn irq(self) -> u8 {
+        self.as_u8() - pics::PIC_1_OFFSET
+    }
+}
+
+// 负责外部中断的中断控制器
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Controller {
+    // 传统的两片级联8259 PIC，启动时默认使用
+    Pic,
+    // 本地APIC加IO APIC，由 `apic::init` 切换
+    Apic,
+}
+
+// `apic::init` 成功后置为 `true`
+static APIC_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+// 当前负责外部中断的中断控制器
+pub fn controller() -> Controller {
+    if APIC_ACTIVE.load(Ordering::Acquire) {
+        Controller::Apic
+    } else {
+        Controller::Pic
+    }
+}
+
 lazy_static! {
     // 定义了一个名为 `IDT` 的静态变量
     static ref IDT: InterruptDescriptorTable = {
         // 使用默认构造函数创建一个新的空白IDT实例
         let mut idt = InterruptDescriptorTable::new();
+        // 按异常向量号顺序设置CPU异常处理函数
+        // 没有处理函数的异常会直接升级为双重异常，只能看到一个笼统的DOUBLE FAULT而丢失真正的原因
+        idt.divide_error.set_handler_fn(divide_error_handler);
+        idt.debug.set_handler_fn(debug_handler);
+        idt.non_maskable_interrupt.set_handler_fn(non_maskable_interrupt_handler);
         // 设置debugger breakpoint (调试器断点异常) 中断处理函数
         idt.breakpoint.set_handler_fn(breakpoint_handler);
+        idt.overflow.set_handler_fn(overflow_handler);
+        idt.bound_range_exceeded.set_handler_fn(bound_range_exceeded_handler);
+        idt.invalid_opcode.set_handler_fn(invalid_opcode_handler);
+        idt.device_not_available.set_handler_fn(device_not_available_handler);
         // 设置double fault (双重错误）异常 对应中断处理功能
-        idt.double_fault.set_handler_fn(double_fault_handler);
-        // 将计时器和键盘中断索引映射到相应处理程序
-        idt[InterruptIndex::Timer.as_usize()].set_handler_fn(time_interrupt_handler);
-        idt[InterruptIndex::Keyboard.as_usize()].set_handler_fn(keyboard_interrupt_handler);
+        // 并指定使用TSS中断栈表里的独立栈：内核栈溢出时原栈已经不可用，如果仍在原栈上压入异常帧会引发三重异常导致重启
+        // `set_stack_index` 需要调用者保证该索引有效且没有被其他异常使用，所以是unsafe的
+        unsafe {
+            idt.double_fault.set_handler_fn(double_fault_handler)
+                .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
+        }
+        idt.invalid_tss.set_handler_fn(invalid_tss_handler);
+        idt.segment_not_present.set_handler_fn(segment_not_present_handler);
+        idt.stack_segment_fault.set_handler_fn(stack_segment_fault_handler);
+        idt.general_protection_fault.set_handler_fn(general_protection_fault_handler);
+        // 缺页异常：处理函数读取CR2中的出错地址并解码错误码，见 `page_fault` 模块
+        idt.page_fault.set_handler_fn(page_fault::page_fault_handler);
+        idt.x87_floating_point.set_handler_fn(x87_floating_point_handler);
+        idt.alignment_check.set_handler_fn(alignment_check_handler);
+        idt.machine_check.set_handler_fn(machine_check_handler);
+        idt.simd_floating_point.set_handler_fn(simd_floating_point_handler);
+        // 外部中断都经过 `irq` 模块分发，具体的处理函数由各驱动通过 `register_irq` 注册
+        for (irq, entry) in irq::ENTRIES.iter().enumerate() {
+            idt[irq::vector(irq as u8) as usize].set_handler_fn(*entry);
+        }
+        // 定时器中断和主动让出CPU的中断需要切换线程，使用 `thread::context` 中保存全部寄存器的汇编入口
+        // `set_handler_addr` 需要调用者保证地址处是一个合法的中断处理入口，所以是unsafe的
+        unsafe {
+            idt[InterruptIndex::Timer.as_usize()].set_handler_addr(thread::context::timer_entry_addr());
+            idt[thread::context::YIELD_INTERRUPT_VECTOR as usize]
+                .set_handler_addr(thread::context::yield_entry_addr());
+        }
+        // 本地APIC的伪中断
+        idt[apic::SPURIOUS_VECTOR as usize].set_handler_fn(spurious_interrupt_handler);
         idt
     };
 }
@@ -54,50 +139,130 @@ extern "x86-interrupt" fn double_fault_handler(_stack_frame: InterruptStackFrame
     loop {}
 }
 
-// 定时器中断处理函数
-// - 每次定时器触发时打印出一个点(`.`)来表示时间流逝。
-// - `unsafe {}` 块包含潜在危险操作：锁定 PIC 控制器并发送 EOI (End Of Interrupt)，告知我们已经完成对当前中断的处理；需要unsafe因为如果错误地发送EOI可能导致中断管理混乱
-extern "x86-interrupt" fn time_interrupt_handler(_stack_frame: InterruptStackFrame) {
-    print!(".");
+// 以下是其余CPU异常的处理函数，按异常向量号排列
+// - 陷阱类异常(debug、NMI、overflow)在处理后可以返回，继续执行下一条指令。
+// - 故障类异常返回后会重新执行出错的指令并再次触发异常，所以打印信息后停在 `hlt_loop` 中，保留现场便于排查。
+// - 带错误码的段相关异常(#TS、#NP、#SS、#GP)的错误码是一个段选择子，使用 `SelectorErrorCode` 解码出表类型和索引。
 
-    unsafe {
-        pics::PICS.lock().notify_end_of_interrupt(pics::InterruptIndex::Timer.as_u8());
-    }
+// #DE 除法错误：除数为0或商超出目标寄存器范围
+extern "x86-interrupt" fn divide_error_handler(stack_frame: InterruptStackFrame) {
+    println!("EXCEPTION: DIVIDE ERROR\n{:#?}", stack_frame);
+    crate::hlt_loop();
 }
 
-// 键盘中断处理函数
-// 使用 `"x86-interrupt"` 调用约定，声明一个键盘中断处理器函数。它接收一个 `InterruptStackFrame` 参数 `_stack_frame`，包含发生中断时的CPU寄存器状态（在此函数不直接使用）
-extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
-    // 在函数内部导入 `pc_keyboard` crate 的相关模块和类型，用于解码键盘扫描码
-    use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
-    // 使用 `lazy_static!` 定义了一个静态的 `KEYBOARD` 变量，它是一个互斥锁（Mutex），保护 `Keyboard` 结构体实例。这个结构体支持美国104键布局和扫描集1，并且选择忽略控制字符（例如Ctrl组合按键
-    lazy_static! {
-        static ref KEYBOARD: Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>> =
-            Mutex::new(Keyboard::new(layouts::Us104Key, ScancodeSet1,
-                HandleControl::Ignore)
-            );
-    }
-    // 通过锁获取对 `KEYBOARD` 的访问权限，并将其赋值给变量 `keyboard` 供后续操作使用
-    let mut keyboard = KEYBOARD.lock();
-    // 创建新的I/O端口对象以读取端口号为0x60的数据，0x60是标准PS/2键盘的数据端口号
-    let mut port = Port::new(0x60);
-    // 从数据端口读取一个字节大小的扫描码。因为I/O端口读写可能与硬件直接交互且无法保证总是安全有效，所以这里需要使用unsafe块
-    let scancode: u8 = unsafe { port.read() };
-    // 将扫描码添加到之前初始化的 `keyboard` 实例中并尝试解析出具体的按键事件。如果成功处理按键事件，则输出相应字符或按键信息。
-    // - 如果成功解析成Unicode字符，则直接打印该字符。
-    // - 如果是特殊按键，则打印其原始按键值的Debug表示形式。
-    if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
-        if let Some(key) = keyboard.process_keyevent(key_event) {
-            match key {
-                DecodedKey::Unicode(character) => print!("{}",character),
-                DecodedKey::RawKey(key) => print!("{:?}", key),
-            }
+// #DB 调试异常：单步执行或硬件断点触发
+extern "x86-interrupt" fn debug_handler(stack_frame: InterruptStackFrame) {
+    println!("EXCEPTION: DEBUG\n{:#?}", stack_frame);
+}
+
+// NMI 不可屏蔽中断：通常由硬件故障(如内存校验错误)或看门狗产生
+extern "x86-interrupt" fn non_maskable_interrupt_handler(stack_frame: InterruptStackFrame) {
+    println!("EXCEPTION: NON-MASKABLE INTERRUPT\n{:#?}", stack_frame);
+}
+
+// #OF 溢出：执行 `INTO` 指令时溢出标志位被置位
+extern "x86-interrupt" fn overflow_handler(stack_frame: InterruptStackFrame) {
+    println!("EXCEPTION: OVERFLOW\n{:#?}", stack_frame);
+}
+
+// #BR 越界：`BOUND` 指令检查到数组下标超出范围
+extern "x86-interrupt" fn bound_range_exceeded_handler(stack_frame: InterruptStackFrame) {
+    println!("EXCEPTION: BOUND RANGE EXCEEDED\n{:#?}", stack_frame);
+    crate::hlt_loop();
+}
+
+// #UD 无效操作码：CPU无法识别的指令，或在当前模式下不允许执行的指令
+extern "x86-interrupt" fn invalid_opcode_handler(stack_frame: InterruptStackFrame) {
+    println!("EXCEPTION: INVALID OPCODE\n{:#?}", stack_frame);
+    crate::hlt_loop();
+}
+
+// #NM 设备不可用：在CR0禁用了FPU/SSE的情况下执行了浮点或SIMD指令
+extern "x86-interrupt" fn device_not_available_handler(stack_frame: InterruptStackFrame) {
+    println!("EXCEPTION: DEVICE NOT AVAILABLE\n{:#?}", stack_frame);
+    crate::hlt_loop();
+}
+
+// #TS 无效TSS：任务切换或使用TSS时发现其中的段选择子无效
+extern "x86-interrupt" fn invalid_tss_handler(stack_frame: InterruptStackFrame, error_code: u64) {
+    println!("EXCEPTION: INVALID TSS\n{}\n{:#?}", SelectorErrorDisplay(error_code), stack_frame);
+    crate::hlt_loop();
+}
+
+// #NP 段不存在：加载的段描述符的存在位(P)为0
+extern "x86-interrupt" fn segment_not_present_handler(stack_frame: InterruptStackFrame, error_code: u64) {
+    println!("EXCEPTION: SEGMENT NOT PRESENT\n{}\n{:#?}", SelectorErrorDisplay(error_code), stack_frame);
+    crate::hlt_loop();
+}
+
+// #SS 栈段错误：栈段不存在，或使用非规范地址访问栈
+extern "x86-interrupt" fn stack_segment_fault_handler(stack_frame: InterruptStackFrame, error_code: u64) {
+    println!("EXCEPTION: STACK SEGMENT FAULT\n{}\n{:#?}", SelectorErrorDisplay(error_code), stack_frame);
+    crate::hlt_loop();
+}
+
+// #GP 通用保护错误：各种权限和段检查失败，例如访问非规范地址、在用户态执行特权指令、写入只读段等
+extern "x86-interrupt" fn general_protection_fault_handler(stack_frame: InterruptStackFrame, error_code: u64) {
+    println!("EXCEPTION: GENERAL PROTECTION FAULT\n{}\n{:#?}", SelectorErrorDisplay(error_code), stack_frame);
+    crate::hlt_loop();
+}
+
+// #MF x87浮点异常：未屏蔽的x87 FPU浮点错误
+extern "x86-interrupt" fn x87_floating_point_handler(stack_frame: InterruptStackFrame) {
+    println!("EXCEPTION: x87 FLOATING POINT\n{:#?}", stack_frame);
+    crate::hlt_loop();
+}
+
+// #AC 对齐检查：开启对齐检查后在用户态进行了未对齐的内存访问，错误码总是0
+extern "x86-interrupt" fn alignment_check_handler(stack_frame: InterruptStackFrame, error_code: u64) {
+    println!("EXCEPTION: ALIGNMENT CHECK\nError Code: {:#x}\n{:#?}", error_code, stack_frame);
+    crate::hlt_loop();
+}
+
+// #MC 机器检查：CPU检测到内部错误或总线错误，不可恢复，所以处理函数不返回
+extern "x86-interrupt" fn machine_check_handler(stack_frame: InterruptStackFrame) -> ! {
+    println!("EXCEPTION: MACHINE CHECK\n{:#?}", stack_frame);
+    crate::hlt_loop();
+}
+
+// #XF SIMD浮点异常：未屏蔽的SSE浮点错误
+extern "x86-interrupt" fn simd_floating_point_handler(stack_frame: InterruptStackFrame) {
+    println!("EXCEPTION: SIMD FLOATING POINT\n{:#?}", stack_frame);
+    crate::hlt_loop();
+}
+
+// #SS和#GP在与段选择子无关时错误码为0，这种情况下直接说明没有关联的选择子，而不是打印一个指向GDT第0项的解码结果
+struct SelectorErrorDisplay(u64);
+
+impl fmt::Display for SelectorErrorDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let code = SelectorErrorCode::new_truncate(self.0);
+        if code.is_null() {
+            write!(f, "Error Code: 0 (not selector related)")
+        } else {
+            write!(f, "{:#?}", code)
         }
     }
-    // 通过向PIC发送EOI（结束中断信号），通知硬件我们已经完成对当前这个中断处理程序的工作。同样地，因为涉及到底层硬件交互操作必须在unsafe块内执行
-    unsafe {
-        pics::PICS.lock().notify_end_of_interrupt(pics::InterruptIndex::Keyboard.as_u8());
-    }
+}
+
+// 定时器中断处理函数
+// 由 `thread::context` 中的汇编入口调用：`saved_rsp` 指向被中断线程保存在栈上的寄存器，返回值是接下来要恢复的线程的栈指针
+// - 先和其他IRQ一样分发：调用 `time` 模块注册的处理函数更新tick计数(调度器使用它计算等待时间)，然后发送EOI
+// - EOI必须在切换线程之前发送，切换之后要到下一个线程再次被中断时才会回到这里
+pub(crate) extern "C" fn time_interrupt_handler(saved_rsp: u64) -> u64 {
+    irq::dispatch(InterruptIndex::Timer.irq());
+
+    thread::on_timer_tick(saved_rsp)
+}
+
+// 本地APIC的伪中断处理函数
+// 中断在被CPU接受之前就被撤销时，APIC会发送伪中断。它没有对应的中断源，不能发送EOI
+extern "x86-interrupt" fn spurious_interrupt_handler(_stack_frame: InterruptStackFrame) {}
+
+#[test_case]
+fn test_breakpoint_exception() {
+    // 触发一个断点异常，断点处理函数返回后应能继续执行
+    x86_64::instructions::interrupts::int3();
 }
 
 // 1. 为什么double_fault_handler和breakpoint_handler不用发送EOI?
@@ -0,0 +1,106 @@
+// 缺页异常(#PF)处理
+// 发生缺页异常时，CPU把引发异常的虚拟地址写入CR2寄存器，并在栈上压入一个错误码，描述这次访问的类型和失败原因。
+// 这里把错误码解码成可读的描述，区分内核态和用户态的缺页，为之后的保护页和按需分页打基础
+
+use core::fmt;
+use x86_64::registers::control::Cr2;
+use x86_64::structures::idt::{InterruptStackFrame, PageFaultErrorCode};
+
+use crate::println;
+
+// 缺页异常的解码结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageFaultInfo {
+    // 引发异常的虚拟地址(CR2)。保留原始值，非规范地址也能完整打印出来
+    pub address: u64,
+    // 页面存在，异常是由权限检查失败引起的；否则是访问了未映射的页面
+    pub present: bool,
+    // 写访问；否则是读访问
+    pub write: bool,
+    // 访问发生在用户态(CPL=3)；否则发生在内核态
+    pub user: bool,
+    // 取指令时发生的异常，例如执行了设置了NO_EXECUTE的页面
+    pub instruction_fetch: bool,
+    // 页表项的保留位被置1，说明页表本身已经损坏
+    pub reserved_bit: bool,
+}
+
+impl PageFaultInfo {
+    pub fn new(address: u64, error_code: PageFaultErrorCode) -> Self {
+        PageFaultInfo {
+            address,
+            present: error_code.contains(PageFaultErrorCode::PROTECTION_VIOLATION),
+            write: error_code.contains(PageFaultErrorCode::CAUSED_BY_WRITE),
+            user: error_code.contains(PageFaultErrorCode::USER_MODE),
+            instruction_fetch: error_code.contains(PageFaultErrorCode::INSTRUCTION_FETCH),
+            reserved_bit: error_code.contains(PageFaultErrorCode::MALFORMED_TABLE),
+        }
+    }
+}
+
+// 输出形如 `kernel write to non-present page at 0xdeadbeaf` 的一行描述
+impl fmt::Display for PageFaultInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mode = if self.user { "user" } else { "kernel" };
+        let access = if self.instruction_fetch {
+            "instruction fetch"
+        } else if self.write {
+            "write"
+        } else {
+            "read"
+        };
+        let reason = if self.present {
+            "protection violation on present page"
+        } else {
+            "non-present page"
+        };
+        write!(f, "{} {} to {} at {:#x}", mode, access, reason, self.address)?;
+        if self.reserved_bit {
+            write!(f, " (reserved bit set in page table entry)")?;
+        }
+        Ok(())
+    }
+}
+
+// 缺页异常处理函数
+// 目前还没有按需分页，也没有用户进程可以终止，所以无论内核态还是用户态的缺页都是致命的：打印完信息后停在 `hlt_loop` 中
+pub extern "x86-interrupt" fn page_fault_handler(stack_frame: InterruptStackFrame, error_code: PageFaultErrorCode) {
+    let info = PageFaultInfo::new(Cr2::read_raw(), error_code);
+
+    // 除了错误码中的USER_MODE位，还可以通过被中断代码的CS寄存器低2位(特权级RPL)判断异常发生在哪个特权级
+    let from_user = info.user || stack_frame.code_segment & 0b11 == 3;
+    if from_user {
+        println!("EXCEPTION: PAGE FAULT (user mode)");
+    } else {
+        println!("EXCEPTION: PAGE FAULT (kernel mode)");
+    }
+    println!("Accessed Address: {:#x}", info.address);
+    println!("{}", info);
+    println!("Error Code: {:?}", error_code);
+    println!("{:#?}", stack_frame);
+    crate::hlt_loop();
+}
+
+#[test_case]
+fn test_decode_kernel_read_not_present() {
+    let info = PageFaultInfo::new(0xdead_b000, PageFaultErrorCode::empty());
+    assert!(!info.present && !info.write && !info.user && !info.instruction_fetch && !info.reserved_bit);
+}
+
+#[test_case]
+fn test_decode_user_write_protection_violation() {
+    let code = PageFaultErrorCode::PROTECTION_VIOLATION
+        | PageFaultErrorCode::CAUSED_BY_WRITE
+        | PageFaultErrorCode::USER_MODE;
+    let info = PageFaultInfo::new(0x1000, code);
+    assert!(info.present && info.write && info.user);
+    assert!(!info.instruction_fetch && !info.reserved_bit);
+}
+
+#[test_case]
+fn test_decode_instruction_fetch_and_reserved_bit() {
+    let code = PageFaultErrorCode::INSTRUCTION_FETCH | PageFaultErrorCode::MALFORMED_TABLE;
+    let info = PageFaultInfo::new(0x2000, code);
+    assert!(info.instruction_fetch && info.reserved_bit);
+    assert!(!info.present && !info.write && !info.user);
+}
@@ -13,25 +13,37 @@ pub static PICS: spin::Mutex<ChainedPics> = spin::Mutex::new(
     unsafe {ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET)}
 );
 
-// 这里通过派生(`derive`)特性给我们的 `InterruptIndex` 枚举添加调试、克隆和复制功能。
-#[derive(Debug, Clone, Copy)]
-// 同时用 `#[repr(u8)]` 属性确保枚举底层数据类型为 u8
-#[repr(u8)]
-pub enum InterruptIndex {
-    // 定义枚举，其中每一项代表重要硬件中断的索引值。首项 'Timer' 设定等同于之上对齐基准静态常量处 (即中断向量起点数)，而 'Keyboard' 自动递增位次序(33)
-    Timer = PIC_1_OFFSET,
-    Keyboard,
+// 打开IRQ `irq` 对应的中断线。从片上的中断(8-15)还要打开主片上连接从片的IRQ2
+pub fn unmask(irq: u8) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut pics = PICS.lock();
+        unsafe {
+            let [mut mask1, mut mask2] = pics.read_masks();
+            if irq < 8 {
+                mask1 &= !(1 << irq);
+            } else {
+                mask1 &= !(1 << 2);
+                mask2 &= !(1 << (irq - 8));
+            }
+            pics.write_masks(mask1, mask2);
+        }
+    });
 }
 
-impl InterruptIndex {
-    // 允许你把枚举内项直接转换成相应的u8数字表达
-    pub fn as_u8(self) -> u8 {
-        self as u8
-    }
-    // 转usize 方法借助已确定拥有从'u8 转换成更通用usize' 操作可能性前提下 获取值 —— 这种设计是经过考虑方便Rust标准库和诸如数组索引等场景使用需要整型下界但数字大小本质不大时采用小占空间排序）
-    pub fn as_usize(self) -> usize {
-        usize::from(self.as_u8())
-    }
+// 屏蔽IRQ `irq` 对应的中断线。主片的IRQ2保持打开，从片上的其他中断线不受影响
+pub fn mask(irq: u8) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut pics = PICS.lock();
+        unsafe {
+            let [mut mask1, mut mask2] = pics.read_masks();
+            if irq < 8 {
+                mask1 |= 1 << irq;
+            } else {
+                mask2 |= 1 << (irq - 8);
+            }
+            pics.write_masks(mask1, mask2);
+        }
+    });
 }
 
 // 1. ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET)
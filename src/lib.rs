@@ -1,19 +1,49 @@
 #![no_std]
 // 表示程序不使用常规的入口点命名（例如 `main` 函数），这是因为大多数操作系统都有自己特定的入口点要求
-#![no_main]
+// 只在测试模式下生效：`cargo test` 时lib.rs会被编译成独立的可执行文件，需要自己提供 `_start`
+#![cfg_attr(test, no_main)]
 // 启用一个尚未稳定的 Rust 功能，允许定义使用 `"x86-interrupt"` 调用约定的函数。这对于设置处理x86中断所需的正确函数签名至关重要
 #![feature(abi_x86_interrupt)]
+// 启用自定义测试框架。默认的test框架依赖标准库，在no_std环境下无法使用
+#![feature(custom_test_frameworks)]
+// 指定收集到的所有 `#[test_case]` 交给 `test_runner` 函数执行
+#![test_runner(crate::test_runner)]
+// 自定义测试框架生成的入口函数默认叫 `main`，但我们用了 `no_main`，所以将其重命名为 `test_main` 并在 `_start` 中手动调用
+#![reexport_test_harness_main = "test_main"]
+// 启用自定义堆分配失败处理函数
+#![feature(alloc_error_handler)]
+
+// 引入 `alloc` crate。它是标准库中只依赖堆分配器的部分，提供 `Box`、`Vec`、`String` 等类型
+extern crate alloc;
+
+use core::panic::PanicInfo;
 
 // 告知编译器应有相应模块存在，并指示它去特定位置寻找这些模块定义
 // - `interrupts`: 处理CPU中断和异常。
 // - `vga_buffer`: 控制文本模式VGA显示缓冲区输出。
 // - `gdt`: 设置全局描述符表(Global Descriptor Table)，它定义了不同内存段(segment)的权限和属性。
+// - `serial`: 通过串口(COM1)输出，QEMU可以将其重定向到宿主机终端。
+// - `memory`: 基于bootloader提供的内存映射管理物理内存和页表。
+// - `allocator`: 内核堆和全局分配器。
+// - `task`: 协作式异步任务和执行器。
+// - `thread`: 由定时器中断抢占调度的内核线程。
+// - `time`: PIT定时器和tick计数，提供开机时间、睡眠和异步延时，以及基于HPET和TSC的高精度时钟。
+// - `acpi`: 查找并解析固件提供的ACPI表(MADT、FADT、HPET)，用于发现平台硬件。
+// - `power`: 通过ACPI或模拟器端口关机，以及多种方式的重启。
 // 这表示正在声明（declare）三个模块：`interrupts`、`vga_buffer` 和 `gdt`。通过使用 `mod` 关键字，告诉 Rust 编译器期望在当前 crate 的文件系统中找到与模块同名的文件或目录。
 // - 如果是文件，则模块的内容将会来自于一个同名的 `.rs` 文件。例如，对于 `mod interrupts;`，编译器会查找一个叫做 `interrupts.rs` 的文件。
 // - 如果是目录，则模块的内容将会来自于该目录下的 `mod.rs` 文件。例如，对于 `mod gdt;` 如果有一个名为 `gdt/` 的目录存在，那么编译器会查找 `gdt/mod.rs
 pub mod interrupts;
 pub mod vga_buffer;
 pub mod gdt;
+pub mod serial;
+pub mod memory;
+pub mod allocator;
+pub mod task;
+pub mod thread;
+pub mod time;
+pub mod acpi;
+pub mod power;
 
 pub fn init() {
     // 加载GDT
@@ -25,6 +55,10 @@ pub fn init() {
     interrupts::init_idt();
     // 初始化可编程中断控制器(PIC)，配置它以接收硬件中断。因为PIC相关操作可能会引起未定义行为，所以需要放在unsafe块内执行。
     unsafe {interrupts::pics::PICS.lock().initialize()};
+    // 设置PIT定时器的频率并注册定时器中断，之后每个定时器中断代表固定的时间
+    time::init();
+    // 注册键盘中断
+    task::keyboard::init();
     // 开启CPU中断，使得CPU能够响应外部设备发起的IRQ和其他形式的硬件请求
     x86_64::instructions::interrupts::enable();
 }
@@ -32,10 +66,99 @@ pub fn init() {
 pub fn hlt_loop() -> !{
     loop {
         // 这个无限循环被设计成一个安全停止执行流程，并等待下一个可用中断事件。每次循环调用汇编指令HLT (Halt)，暂停CPU执行直到发生下一次硬件中断。返回类型 `!` 表示该函数永远不会返回
-        x86_64::instructions::hlt;
+        x86_64::instructions::hlt();
+    }
+}
+
+// QEMU退出码
+// 配合QEMU的 `isa-debug-exit` 设备使用：向其I/O端口写入值 `value` 后，QEMU会以 `(value << 1) | 1` 作为进程退出码退出。
+// 所以 Success(0x10) 对应退出码33，Failed(0x11) 对应退出码35，都不会和QEMU自身使用的0、1冲突
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum QemuExitCode {
+    Success = 0x10,
+    Failed = 0x11,
+}
+
+// 向 `isa-debug-exit` 设备的端口(0xf4，在Cargo.toml的test-args中配置)写入退出码，使QEMU立即退出
+pub fn exit_qemu(exit_code: QemuExitCode) {
+    use x86_64::instructions::port::Port;
+
+    unsafe {
+        let mut port = Port::new(0xf4);
+        port.write(exit_code as u32);
+    }
+}
+
+// 可被测试框架运行的测试
+// 为所有 `Fn()` 实现该trait，这样 `#[test_case]` 标注的普通函数就会自动打印自己的名字和结果，不需要在每个测试里手动输出
+pub trait Testable {
+    fn run(&self);
+}
+
+impl<T> Testable for T
+where
+    T: Fn(),
+{
+    fn run(&self) {
+        // `type_name` 返回函数的完整路径，例如 `cjn_os::vga_buffer::test_println_simple`
+        serial_print!("{}...\t", core::any::type_name::<T>());
+        self();
+        serial_println!("[ok]");
     }
 }
 
+// 测试运行器
+// 依次执行所有测试，全部通过后以成功码退出QEMU；任何一个测试panic都会进入 `panic` 处理函数并以失败码退出
+pub fn test_runner(tests: &[&dyn Testable]) {
+    serial_println!("Running {} tests", tests.len());
+    for test in tests {
+        test.run();
+    }
+    exit_qemu(QemuExitCode::Success);
+}
+
+// 测试模式下的panic处理逻辑：通过串口输出错误信息后以失败码退出QEMU
+// 公开出来供 `main.rs` 和 `tests/` 下的集成测试在各自的 `#[panic_handler]` 中复用
+pub fn test_panic_handler(info: &PanicInfo) -> ! {
+    serial_println!("[failed]\n");
+    serial_println!("Error: {}\n", info);
+    exit_qemu(QemuExitCode::Failed);
+    hlt_loop();
+}
+
+// `cargo test --lib` 时的入口点
+// 使用 `entry_point!` 宏让编译器检查入口函数的签名，bootloader会把 `BootInfo` 传进来
+#[cfg(test)]
+use bootloader::{entry_point, BootInfo};
+
+#[cfg(test)]
+entry_point!(test_kernel_main);
+
+#[cfg(test)]
+fn test_kernel_main(boot_info: &'static BootInfo) -> ! {
+    init();
+    unsafe { memory::init(boot_info) };
+    allocator::init_heap().expect("heap initialization failed");
+    vga_buffer::init_scrollback();
+    time::init_high_resolution();
+    thread::init(thread::scheduler::BOOT_POLICY);
+    test_main();
+    hlt_loop();
+}
+
+#[cfg(test)]
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    test_panic_handler(info)
+}
+
+#[test_case]
+#[allow(clippy::eq_op)]
+fn trivial_assertion() {
+    assert_eq!(1, 1);
+}
+
 
 
 // 1. #![no_std]是工程里每个rs都要使用吗？为什么有的rs没使用？有的rs比如这个lib.rs又使用
@@ -2,24 +2,84 @@
 #![no_std] // 不链接Rust标准库
 #![no_main] // 禁用所有Rust层级的入口点
 #![feature(abi_x86_interrupt)]
+// 二进制crate同样使用lib中定义的自定义测试框架
+#![feature(custom_test_frameworks)]
+#![test_runner(cjn_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
 
 use core::panic::PanicInfo;
+use bootloader::{entry_point, BootInfo};
 #[warn(unused_imports)]
 use cjn_os::println;
+use cjn_os::allocator;
+use cjn_os::interrupts::apic::{self, TimerSource};
+use cjn_os::memory;
+use cjn_os::task::executor::Executor;
+use cjn_os::task::{keyboard, Task};
+use cjn_os::thread;
+use cjn_os::thread::scheduler::BOOT_POLICY;
+use cjn_os::time;
 use cjn_os::vga_buffer;
 
 // 将会在panic时调用
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(_info: &PanicInfo) -> ! {
-    println!("{}", _info);
+    // 先输出到串口，无显示器运行时或者VGA控制台出了问题时也能在宿主机上看到panic信息
+    cjn_os::serial::panic_print(format_args!("{}\n", _info));
+    // 再写到内核控制台并尽量切换到前台。panic可能发生在持有控制台锁的时候，这里不会等待任何锁
+    vga_buffer::panic_print(format_args!("{}\n", _info));
     cjn_os::hlt_loop();
 }
 
+// 测试模式下panic时调用：复用lib中的测试panic处理逻辑
+#[cfg(test)]
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cjn_os::test_panic_handler(info)
+}
+
 
-#[no_mangle] //不重整函数名
-// 定义一个符合C调用规范的公开函数 `_start`。由于使用 `-> !` 表明这个函数永不返回.
-pub extern "C" fn _start() -> ! {
+// bootloader会以 `BootInfo` 作为参数调用内核入口。直接定义 `extern "C" fn _start(boot_info: &'static BootInfo)` 时编译器无法检查参数类型，
+// 所以使用 `entry_point!` 宏：它会生成真正的 `_start` 函数并以类型安全的方式调用 `kernel_main`
+entry_point!(kernel_main);
+
+// 内核入口函数。由于使用 `-> !` 表明这个函数永不返回.
+// - `boot_info`: bootloader传来的启动信息，其中的 `memory_map` 描述了物理内存布局，`physical_memory_offset` 是全部物理内存被映射到的虚拟地址
+fn kernel_main(boot_info: &'static BootInfo) -> ! {
     vga_buffer::print_something();
-    // 进入无限循环防止 `_start` 函数,返回也确保内核不会意外退出到未定义行为状态中去
-    cjn_os::hlt_loop();
+
+    // 加载GDT、IDT，初始化PIC并开启中断
+    cjn_os::init();
+
+    // 根据bootloader提供的内存映射初始化物理帧分配器和页表映射器
+    unsafe { memory::init(boot_info) };
+    println!("Usable memory: {} KiB", memory::usable_memory() / 1024);
+    // 映射内核堆，之后就可以使用 `Box`、`Vec` 等需要堆分配的类型了
+    allocator::init_heap().expect("heap initialization failed");
+    // 之后滚出屏幕的输出可以用Shift+PageUp/PageDown翻看
+    vga_buffer::init_scrollback();
+    // 用HPET(或PIT)校准TSC，提供纳秒级的 `time::Instant`
+    let reference = time::init_high_resolution();
+    if let Some(hz) = time::tsc::frequency() {
+        println!("TSC: {} MHz (calibrated against {:?})", hz / 1_000_000, reference);
+    }
+    // 从8259 PIC切换到APIC，定时器中断改由本地APIC定时器产生。平台不支持时继续使用PIC
+    match apic::init(TimerSource::LocalApic) {
+        Ok(()) => println!("Interrupt controller: APIC"),
+        Err(err) => println!("Interrupt controller: 8259 PIC ({:?})", err),
+    }
+    // 登记当前执行流为引导线程，之后定时器中断就会在各内核线程之间切换
+    // 调度策略由 `sched-*` 特性选择，例如 `cargo run --features sched-fair-share`
+    thread::init(BOOT_POLICY);
+    println!("Scheduler: {}", thread::scheduler_name());
+
+    #[cfg(test)]
+    test_main();
+
+    // 运行异步执行器。它永不返回，没有就绪任务时通过 `hlt` 休眠等待中断，也确保内核不会意外退出到未定义行为状态中去
+    let mut executor = Executor::new();
+    // 键盘输入的解码和显示
+    executor.spawn(Task::new(keyboard::print_keypresses()));
+    executor.run();
 }
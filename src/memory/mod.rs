@@ -0,0 +1,102 @@
+// 物理内存管理
+// bootloader在跳转到内核之前通过BIOS的E820功能探测了物理内存布局，并把结果作为 `MemoryMap` 放在 `BootInfo` 中传给内核。
+// 这里基于这份内存映射实现一个物理帧分配器，为之后的分页和堆分配提供物理内存
+
+pub mod paging;
+
+// `BootInfo` 包含内存映射和物理内存映射的起始偏移
+use bootloader::BootInfo;
+// 全局帧分配器的互斥锁
+use spin::Mutex;
+// 从bootloader crate导入内存映射相关的类型：`MemoryMap` 是所有内存区域的列表，`MemoryRegionType` 表示每个区域的用途(可用、被内核占用、保留等)
+use bootloader::bootinfo::{MemoryMap, MemoryRegion, MemoryRegionType};
+// `FrameAllocator` trait 是 `x86_64` crate 中页表映射函数申请物理帧时使用的接口；`PhysFrame` 表示一个物理帧；`Size4KiB` 表示标准的4KiB页大小
+use x86_64::structures::paging::{FrameAllocator, PhysFrame, Size4KiB};
+use x86_64::{PhysAddr, VirtAddr};
+
+// 4KiB物理帧的大小
+const FRAME_SIZE: u64 = 4096;
+
+// 内核唯一的物理帧分配器。在 `init` 之前为 `None`
+// 和 `paging` 中的映射器一样放在全局的自旋锁里，这样建立映射时不需要在各个模块之间传递分配器
+pub static FRAME_ALLOCATOR: Mutex<Option<BootInfoFrameAllocator>> = Mutex::new(None);
+
+// 初始化物理内存管理和页表映射
+/// # Safety
+///
+/// 调用者必须保证 `boot_info` 来自bootloader(内存映射可信、全部物理内存已映射到偏移处)，并且只调用一次
+pub unsafe fn init(boot_info: &'static BootInfo) {
+    *FRAME_ALLOCATOR.lock() = Some(BootInfoFrameAllocator::init(&boot_info.memory_map));
+    paging::init(VirtAddr::new(boot_info.physical_memory_offset));
+}
+
+// 可用物理内存的总字节数，帧分配器未初始化时返回0
+pub fn usable_memory() -> u64 {
+    FRAME_ALLOCATOR
+        .lock()
+        .as_ref()
+        .map_or(0, |allocator| allocator.usable_memory())
+}
+
+// 从bootloader内存映射中返回可用帧的帧分配器
+// 只分配不回收：依次遍历每个可用区域，用 `region` 和 `next_addr` 记录当前分配到的位置，每次分配都是O(1)，不需要从头重新遍历整个内存映射
+pub struct BootInfoFrameAllocator {
+    memory_map: &'static MemoryMap,
+    // 当前正在分配的内存区域在 `memory_map` 中的下标
+    region: usize,
+    // 下一个待分配帧的起始物理地址
+    next_addr: u64,
+}
+
+impl BootInfoFrameAllocator {
+    // 使用传入的内存映射创建一个帧分配器
+    /// # Safety
+    ///
+    /// 调用者必须保证传入的内存映射是有效的，尤其是所有标记为 `Usable` 的帧确实没有被使用，
+    /// 并且整个内核中只创建一个分配器，否则同一个物理帧可能被分配两次
+    pub unsafe fn init(memory_map: &'static MemoryMap) -> Self {
+        BootInfoFrameAllocator {
+            memory_map,
+            region: 0,
+            next_addr: 0,
+        }
+    }
+
+    // 可用物理内存的总字节数
+    pub fn usable_memory(&self) -> u64 {
+        usable_regions(self.memory_map)
+            .map(|r| r.range.end_addr() - r.range.start_addr())
+            .sum()
+    }
+}
+
+// 返回内存映射中所有标记为可用的区域
+fn usable_regions(memory_map: &'static MemoryMap) -> impl Iterator<Item = &'static MemoryRegion> {
+    memory_map
+        .iter()
+        .filter(|r| r.region_type == MemoryRegionType::Usable)
+}
+
+// `FrameAllocator` 是unsafe trait，实现者必须保证分配出去的帧都是未被使用的
+unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame> {
+        while let Some(region) = self.memory_map.get(self.region) {
+            if region.region_type == MemoryRegionType::Usable {
+                // 第一次进入该区域时从区域起始地址开始分配
+                let start = region.range.start_addr();
+                if self.next_addr < start {
+                    self.next_addr = start;
+                }
+                if self.next_addr + FRAME_SIZE <= region.range.end_addr() {
+                    let frame = PhysFrame::containing_address(PhysAddr::new(self.next_addr));
+                    self.next_addr += FRAME_SIZE;
+                    return Some(frame);
+                }
+            }
+            // 当前区域已经分配完或不可用，继续下一个区域
+            self.region += 1;
+        }
+        // 所有可用物理内存都已分配完
+        None
+    }
+}
@@ -0,0 +1,220 @@
+// 页表管理
+// bootloader开启了 `map_physical_memory` 特性后，会把全部物理内存映射到虚拟地址空间中从 `physical_memory_offset` 开始的一段区域，
+// 所以任何物理地址 `phys` 都可以通过虚拟地址 `physical_memory_offset + phys` 访问。`OffsetPageTable` 利用这一点直接读写各级页表
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::{Mutex, Once};
+// 从 `x86_64` crate 导入页表相关的类型：
+// - `Mapper`/`Translate`: 建立、取消映射和地址转换的trait
+// - `OffsetPageTable`: 基于物理内存偏移访问页表的 `Mapper` 实现
+// - `Page`/`PhysFrame`: 虚拟页和物理帧
+// - `PageTableFlags`: 页表项的标志位(存在、可写、用户可访问等)
+// - `MapToError`/`UnmapError`/`FlagUpdateError`: 各操作可能出现的错误
+use x86_64::structures::paging::mapper::{FlagUpdateError, MapToError, UnmapError};
+use x86_64::structures::paging::{
+    FrameAllocator, Mapper, OffsetPageTable, Page, PageTable, PageTableFlags, PhysFrame, Size4KiB,
+    Translate,
+};
+use x86_64::{PhysAddr, VirtAddr};
+
+use super::FRAME_ALLOCATOR;
+
+// 设备MMIO区域映射到的虚拟地址窗口的起始处。bootloader只映射了物理内存所在的范围，APIC等设备的寄存器位于物理地址空间的高处，
+// 需要单独映射，并且映射时要关闭缓存
+const MMIO_START: u64 = 0x_6666_0000_0000;
+
+// 内核唯一的页表映射器。在 `init` 之前为 `None`
+static MAPPER: Mutex<Option<OffsetPageTable<'static>>> = Mutex::new(None);
+// 物理内存在虚拟地址空间中的起始偏移，初始化后不再改变，所以不需要加锁
+static PHYSICAL_MEMORY_OFFSET: Once<VirtAddr> = Once::new();
+
+// 初始化页表映射器
+/// # Safety
+///
+/// 调用者必须保证全部物理内存确实被映射到了 `physical_memory_offset` 处，并且只能调用一次，
+/// 否则会出现多个指向同一个4级页表的 `&mut` 引用
+pub unsafe fn init(physical_memory_offset: VirtAddr) {
+    PHYSICAL_MEMORY_OFFSET.call_once(|| physical_memory_offset);
+    let level_4_table = active_level_4_table(physical_memory_offset);
+    *MAPPER.lock() = Some(OffsetPageTable::new(level_4_table, physical_memory_offset));
+}
+
+// 返回当前活动的4级页表的可变引用
+// CR3寄存器保存着4级页表的物理帧，加上偏移即可得到它的虚拟地址
+unsafe fn active_level_4_table(physical_memory_offset: VirtAddr) -> &'static mut PageTable {
+    use x86_64::registers::control::Cr3;
+
+    let (level_4_table_frame, _) = Cr3::read();
+
+    let phys = level_4_table_frame.start_address();
+    let virt = physical_memory_offset + phys.as_u64();
+    let page_table_ptr: *mut PageTable = virt.as_mut_ptr();
+
+    &mut *page_table_ptr
+}
+
+// 在持有映射器和帧分配器锁的情况下执行 `f`
+// 关闭中断防止中断处理函数里再次访问页表时死锁。锁的获取顺序固定为先映射器后帧分配器
+fn with_mapper<R>(f: impl FnOnce(&mut OffsetPageTable<'static>, &mut dyn FrameAllocator<Size4KiB>) -> R) -> R {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut mapper = MAPPER.lock();
+        let mut frame_allocator = FRAME_ALLOCATOR.lock();
+        f(
+            mapper.as_mut().expect("paging not initialized"),
+            frame_allocator.as_mut().expect("frame allocator not initialized"),
+        )
+    })
+}
+
+// 把物理地址转换为直接映射区域中对应的虚拟地址
+pub fn phys_to_virt(addr: PhysAddr) -> VirtAddr {
+    let offset = PHYSICAL_MEMORY_OFFSET.get().expect("paging not initialized");
+    *offset + addr.as_u64()
+}
+
+// 把虚拟地址转换为其映射到的物理地址，未映射时返回 `None`
+pub fn translate_addr(addr: VirtAddr) -> Option<PhysAddr> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        MAPPER.lock().as_ref().expect("paging not initialized").translate_addr(addr)
+    })
+}
+
+// 为 `page` 分配一个新的物理帧并建立映射，返回分配到的帧
+// 新帧此前没有被任何地方使用，不会产生别名，所以这个函数是安全的。用于内核堆、用户页等需要新内存的场景
+// 帧分配器不支持回收，所以先检查 `page` 是否已经映射，避免分配了帧却映射失败。
+// 检查之后 `map_to` 仍然可能失败(分配中间页表时内存耗尽，或上级页表项是大页)，这时分配到的帧会被泄漏
+pub fn map_page(page: Page, flags: PageTableFlags) -> Result<PhysFrame, MapToError<Size4KiB>> {
+    with_mapper(|mapper, frame_allocator| {
+        if let Ok(frame) = mapper.translate_page(page) {
+            return Err(MapToError::PageAlreadyMapped(frame));
+        }
+        let frame = frame_allocator
+            .allocate_frame()
+            .ok_or(MapToError::FrameAllocationFailed)?;
+        unsafe { mapper.map_to(page, frame, flags, frame_allocator)?.flush() };
+        Ok(frame)
+    })
+}
+
+// 为 `start` 开始、长度为 `size` 字节的虚拟地址范围中的每一页分配物理帧并建立映射
+pub fn map_range(start: VirtAddr, size: u64, flags: PageTableFlags) -> Result<(), MapToError<Size4KiB>> {
+    // 长度为0时没有需要映射的页，`start + size - 1` 也会指向 `start` 之前
+    if size == 0 {
+        return Ok(());
+    }
+    let start_page: Page = Page::containing_address(start);
+    let end_page: Page = Page::containing_address(start + size - 1u64);
+    for page in Page::range_inclusive(start_page, end_page) {
+        map_page(page, flags)?;
+    }
+    Ok(())
+}
+
+// 把 `page` 映射到指定的物理帧 `frame`，用于访问设备MMIO区域等固定物理地址。映射MMIO区域时通常还需要加上 `NO_CACHE` 标志
+/// # Safety
+///
+/// 调用者必须保证 `frame` 没有以其他方式被使用：
+/// 把同一个普通内存帧映射到两个页面会产生别名，破坏Rust的内存安全保证
+pub unsafe fn map_to(page: Page, frame: PhysFrame, flags: PageTableFlags) -> Result<(), MapToError<Size4KiB>> {
+    with_mapper(|mapper, frame_allocator| {
+        mapper.map_to(page, frame, flags, frame_allocator)?.flush();
+        Ok(())
+    })
+}
+
+// 把从 `phys` 开始、长度为 `size` 字节的设备MMIO区域映射到MMIO窗口中，返回 `phys` 对应的虚拟地址
+// 映射关闭了缓存(`NO_CACHE | WRITE_THROUGH`)，保证每次读写都直接到达设备。虚拟地址只分配不回收
+/// # Safety
+///
+/// 调用者必须保证 `phys` 处确实是设备寄存器而不是普通内存，否则会与直接映射区域产生别名
+pub unsafe fn map_mmio(phys: PhysAddr, size: u64) -> Result<VirtAddr, MapToError<Size4KiB>> {
+    // 下一个MMIO映射使用的虚拟地址
+    static NEXT_MMIO: AtomicU64 = AtomicU64::new(MMIO_START);
+
+    let start_frame: PhysFrame = PhysFrame::containing_address(phys);
+    let end_frame: PhysFrame = PhysFrame::containing_address(phys + size.max(1) - 1u64);
+    let frames = PhysFrame::range_inclusive(start_frame, end_frame);
+    let pages = frames.count() as u64;
+    let virt_start = VirtAddr::new(NEXT_MMIO.fetch_add(pages * 4096, Ordering::Relaxed));
+
+    let flags = PageTableFlags::PRESENT
+        | PageTableFlags::WRITABLE
+        | PageTableFlags::NO_CACHE
+        | PageTableFlags::WRITE_THROUGH;
+    for (i, frame) in PhysFrame::range_inclusive(start_frame, end_frame).enumerate() {
+        let page: Page = Page::containing_address(virt_start + i as u64 * 4096);
+        map_to(page, frame, flags)?;
+    }
+
+    Ok(virt_start + (phys - start_frame.start_address()))
+}
+
+// 取消 `page` 的映射并刷新TLB，返回它原本映射到的物理帧
+// 目前的帧分配器不支持回收，所以由调用者决定如何处理返回的帧
+pub fn unmap_page(page: Page) -> Result<PhysFrame, UnmapError> {
+    with_mapper(|mapper, _| {
+        let (frame, flush) = mapper.unmap(page)?;
+        flush.flush();
+        Ok(frame)
+    })
+}
+
+// 把已映射的 `page` 的标志位修改为 `flags` 并刷新TLB
+/// # Safety
+///
+/// 修改标志位可能使现有的引用失效，例如去掉 `WRITABLE` 后，仍然持有的 `&mut` 写入会触发缺页异常。
+/// 调用者必须保证 `page` 上没有会因此失效的引用
+pub unsafe fn update_flags(page: Page, flags: PageTableFlags) -> Result<(), FlagUpdateError> {
+    with_mapper(|mapper, _| {
+        mapper.update_flags(page, flags)?.flush();
+        Ok(())
+    })
+}
+
+#[test_case]
+fn test_translate_vga_buffer() {
+    // bootloader把VGA文本缓冲区恒等映射，虚拟地址与物理地址相同
+    let phys = translate_addr(VirtAddr::new(0xb8000));
+    assert_eq!(phys, Some(PhysAddr::new(0xb8000)));
+}
+
+#[test_case]
+fn test_phys_to_virt_matches_translation() {
+    // 直接映射区域中的虚拟地址应该被转换回同一个物理地址
+    let phys = PhysAddr::new(0xb8000);
+    assert_eq!(translate_addr(phys_to_virt(phys)), Some(phys));
+}
+
+#[test_case]
+fn test_map_update_unmap_page() {
+    // 选择一个远离内核、bootloader和物理内存映射的地址
+    let page: Page = Page::containing_address(VirtAddr::new(0x_5555_0000_0000));
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+
+    let frame = map_page(page, flags).expect("map_page failed");
+    assert_eq!(translate_addr(page.start_address()), Some(frame.start_address()));
+
+    // 写入后应能读回同样的值
+    let ptr: *mut u64 = page.start_address().as_mut_ptr();
+    unsafe {
+        ptr.write_volatile(0xdead_beef);
+        assert_eq!(ptr.read_volatile(), 0xdead_beef);
+    }
+
+    // 改为只读后映射仍然存在
+    unsafe { update_flags(page, PageTableFlags::PRESENT).expect("update_flags failed") };
+    assert_eq!(translate_addr(page.start_address()), Some(frame.start_address()));
+
+    assert_eq!(unmap_page(page).expect("unmap_page failed"), frame);
+    assert_eq!(translate_addr(page.start_address()), None);
+}
+
+#[test_case]
+fn test_map_range_empty() {
+    // 长度为0时不映射任何页，包括 `start` 之前的那一页
+    let start = VirtAddr::new(0x_5555_0001_0000);
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+    map_range(start, 0, flags).expect("map_range failed");
+    assert_eq!(translate_addr(start), None);
+    assert_eq!(translate_addr(start - 1u64), None);
+}
@@ -0,0 +1,243 @@
+// 关机和重启
+// - `shutdown`: 按ACPI规范进入S5(软关机)状态：从DSDT的 `\_S5` 对象取得睡眠类型，写入FADT给出的PM1控制寄存器。
+//   ACPI不可用时尝试QEMU(0x604)和Bochs/旧版QEMU(0xB004)的关机端口
+// - `reboot`: 依次尝试ACPI复位寄存器、8042键盘控制器的复位脉冲，最后故意触发三重异常让CPU复位
+// 两者都不会返回，所有方法都失败时停在 `hlt_loop` 中
+
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+use x86_64::PhysAddr;
+
+use crate::acpi::{self, GenericAddress};
+use crate::memory::paging;
+
+// PM1控制寄存器中的位
+// SCI_EN: 已经处于ACPI模式
+const PM1_SCI_EN: u16 = 1;
+// SLP_TYP: 第10-12位，睡眠类型
+const PM1_SLP_TYP_SHIFT: u16 = 10;
+// SLP_EN: 写1后进入SLP_TYP指定的睡眠状态
+const PM1_SLP_EN: u16 = 1 << 13;
+
+// 模拟器的关机端口，写入 `0x2000` 即关机
+const QEMU_SHUTDOWN_PORT: u16 = 0x604;
+const BOCHS_SHUTDOWN_PORT: u16 = 0xb004;
+const EMULATOR_SHUTDOWN_VALUE: u16 = 0x2000;
+
+// 8042键盘控制器的状态/命令端口。状态的第1位表示输入缓冲区满，命令0xFE让它拉低CPU的复位线
+const KBC_PORT: u16 = 0x64;
+const KBC_INPUT_FULL: u8 = 1 << 1;
+const KBC_PULSE_RESET: u8 = 0xfe;
+
+// 等待硬件响应时的最大轮询次数
+const SPIN_LIMIT: u32 = 100_000;
+
+// 关闭计算机
+pub fn shutdown() -> ! {
+    interrupts::disable();
+
+    if let Some((slp_typ_a, slp_typ_b)) = acpi_sleep_types() {
+        acpi_shutdown(slp_typ_a, slp_typ_b);
+    }
+
+    // ACPI关机失败或不可用时尝试模拟器的关机端口
+    unsafe {
+        Port::<u16>::new(QEMU_SHUTDOWN_PORT).write(EMULATOR_SHUTDOWN_VALUE);
+        Port::<u16>::new(BOCHS_SHUTDOWN_PORT).write(EMULATOR_SHUTDOWN_VALUE);
+    }
+
+    crate::println!("shutdown failed, halting");
+    crate::hlt_loop();
+}
+
+// 重启计算机
+pub fn reboot() -> ! {
+    interrupts::disable();
+
+    // 1. ACPI复位寄存器
+    if let Some(fadt) = acpi::init().ok().and_then(|acpi| acpi.fadt.as_ref()) {
+        if let Some(reset) = fadt.reset_register {
+            write_reset_register(&reset, fadt.reset_value);
+        }
+    }
+
+    // 2. 8042键盘控制器：等待输入缓冲区为空，再发送复位脉冲命令
+    unsafe {
+        let mut kbc = Port::<u8>::new(KBC_PORT);
+        for _ in 0..SPIN_LIMIT {
+            if kbc.read() & KBC_INPUT_FULL == 0 {
+                break;
+            }
+        }
+        kbc.write(KBC_PULSE_RESET);
+    }
+    spin_wait();
+
+    // 3. 加载一个空的IDT后触发中断：CPU找不到处理函数，引发双重异常，处理双重异常时再次失败引发三重异常，CPU复位
+    triple_fault();
+}
+
+// 从DSDT中读取S5状态的睡眠类型，ACPI不可用时返回 `None`
+fn acpi_sleep_types() -> Option<(u8, u8)> {
+    let fadt = acpi::init().ok()?.fadt.as_ref()?;
+    if fadt.dsdt == 0 || fadt.pm1a_control_block.address == 0 {
+        return None;
+    }
+    // DSDT的地址来自通过校验的FADT
+    let dsdt = unsafe { acpi::sdt_bytes(fadt.dsdt) };
+    if &dsdt[0..4] != b"DSDT" || !acpi::checksum_ok(dsdt) {
+        return None;
+    }
+    find_s5_sleep_types(dsdt)
+}
+
+// 切换到ACPI模式，然后写入PM1a(和PM1b)控制寄存器进入S5
+fn acpi_shutdown(slp_typ_a: u8, slp_typ_b: u8) {
+    let fadt = match acpi::tables().and_then(|acpi| acpi.fadt.as_ref()) {
+        Some(fadt) => fadt,
+        None => return,
+    };
+    // PM1控制寄存器是FADT声明的设备寄存器
+    let mut pm1a = match unsafe { Pm1Register::new(&fadt.pm1a_control_block) } {
+        Some(pm1a) => pm1a,
+        None => return,
+    };
+    unsafe {
+        // SCI_EN为0说明固件仍处于传统模式，需要通过SMI命令端口请求切换到ACPI模式
+        if pm1a.read() & PM1_SCI_EN == 0 && fadt.smi_command_port != 0 && fadt.acpi_enable != 0 {
+            Port::<u8>::new(fadt.smi_command_port as u16).write(fadt.acpi_enable);
+            for _ in 0..SPIN_LIMIT {
+                if pm1a.read() & PM1_SCI_EN != 0 {
+                    break;
+                }
+            }
+        }
+
+        pm1a.write((u16::from(slp_typ_a) << PM1_SLP_TYP_SHIFT) | PM1_SLP_EN);
+        if fadt.pm1b_control_block.address != 0 {
+            if let Some(mut pm1b) = Pm1Register::new(&fadt.pm1b_control_block) {
+                pm1b.write((u16::from(slp_typ_b) << PM1_SLP_TYP_SHIFT) | PM1_SLP_EN);
+            }
+        }
+    }
+    spin_wait();
+}
+
+// 16位的PM1控制寄存器，和复位寄存器一样可能位于I/O端口或MMIO
+enum Pm1Register {
+    Io(Port<u16>),
+    Memory(*mut u16),
+}
+
+impl Pm1Register {
+    // 按通用地址结构的地址空间访问寄存器，不支持的地址空间或映射失败时返回 `None`
+    /// # Safety
+    ///
+    /// 调用者必须保证 `gas` 描述的是一个PM1控制寄存器(例如来自FADT)
+    unsafe fn new(gas: &GenericAddress) -> Option<Self> {
+        match gas.address_space {
+            GenericAddress::SYSTEM_IO => Some(Pm1Register::Io(Port::new(gas.address as u16))),
+            GenericAddress::SYSTEM_MEMORY => {
+                let virt = paging::map_mmio(PhysAddr::new(gas.address), 2).ok()?;
+                Some(Pm1Register::Memory(virt.as_mut_ptr()))
+            }
+            _ => None,
+        }
+    }
+
+    unsafe fn read(&mut self) -> u16 {
+        match self {
+            Pm1Register::Io(port) => port.read(),
+            Pm1Register::Memory(ptr) => ptr.read_volatile(),
+        }
+    }
+
+    unsafe fn write(&mut self, value: u16) {
+        match self {
+            Pm1Register::Io(port) => port.write(value),
+            Pm1Register::Memory(ptr) => ptr.write_volatile(value),
+        }
+    }
+}
+
+// 向ACPI复位寄存器写入复位值，寄存器可能位于I/O端口或MMIO
+fn write_reset_register(reset: &GenericAddress, value: u8) {
+    match reset.address_space {
+        GenericAddress::SYSTEM_IO => unsafe { Port::<u8>::new(reset.address as u16).write(value) },
+        GenericAddress::SYSTEM_MEMORY => {
+            // 复位寄存器是FADT声明的设备寄存器
+            if let Ok(virt) = unsafe { paging::map_mmio(PhysAddr::new(reset.address), 1) } {
+                unsafe { virt.as_mut_ptr::<u8>().write_volatile(value) };
+            }
+        }
+        _ => return,
+    }
+    spin_wait();
+}
+
+// 给硬件一点时间响应，之后还在运行说明这种方法没有生效
+fn spin_wait() {
+    for _ in 0..SPIN_LIMIT {
+        core::hint::spin_loop();
+    }
+}
+
+fn triple_fault() -> ! {
+    use x86_64::instructions::tables::{lidt, DescriptorTablePointer};
+    use x86_64::VirtAddr;
+
+    let empty = DescriptorTablePointer {
+        limit: 0,
+        base: VirtAddr::new(0),
+    };
+    unsafe { lidt(&empty) };
+    x86_64::instructions::interrupts::int3();
+    crate::hlt_loop();
+}
+
+// 在DSDT的AML字节码中查找 `\_S5` 对象，返回其中的SLP_TYPa和SLP_TYPb
+// 完整解析AML需要一个解释器，这里只识别固件通常生成的形式：
+// `NameOp(0x08) ['\\'] "_S5_" PackageOp(0x12) PkgLength NumElements 值a 值b ...`，
+// 其中每个值是 `BytePrefix(0x0A) 字节`，或者直接是 `ZeroOp(0x00)`/`OneOp(0x01)`
+pub fn find_s5_sleep_types(aml: &[u8]) -> Option<(u8, u8)> {
+    let position = aml.windows(4).position(|window| window == b"_S5_")?;
+    // 名字前面必须是NameOp，或者是NameOp加上根前缀 `\`
+    let is_name = (position >= 1 && aml[position - 1] == 0x08)
+        || (position >= 2 && aml[position - 1] == b'\\' && aml[position - 2] == 0x08);
+    let mut offset = position + 4;
+    if !is_name || aml.get(offset) != Some(&0x12) {
+        return None;
+    }
+    offset += 1;
+    // PkgLength：第一个字节的高2位表示后面还有几个字节
+    let lead = *aml.get(offset)?;
+    offset += 1 + usize::from(lead >> 6);
+    // NumElements
+    offset += 1;
+
+    let mut read_value = || -> Option<u8> {
+        let byte = *aml.get(offset)?;
+        if byte == 0x0a {
+            offset += 2;
+            aml.get(offset - 1).copied()
+        } else {
+            offset += 1;
+            Some(byte)
+        }
+    };
+    let slp_typ_a = read_value()?;
+    let slp_typ_b = read_value()?;
+    Some((slp_typ_a & 0x7, slp_typ_b & 0x7))
+}
+
+#[test_case]
+fn test_find_s5_sleep_types() {
+    // QEMU的DSDT: Name (_S5, Package (0x04) { Zero, Zero, Zero, Zero })
+    let qemu = [0x10, 0x08, b'_', b'S', b'5', b'_', 0x12, 0x06, 0x04, 0x00, 0x00, 0x00, 0x00];
+    assert_eq!(find_s5_sleep_types(&qemu), Some((0, 0)));
+    // 带根前缀和BytePrefix的形式: Name (\_S5, Package () { 0x05, 0x05, ... })
+    let prefixed = [0x08, b'\\', b'_', b'S', b'5', b'_', 0x12, 0x0a, 0x04, 0x0a, 0x05, 0x0a, 0x05, 0x00, 0x00];
+    assert_eq!(find_s5_sleep_types(&prefixed), Some((5, 5)));
+    // 作为方法名或字符串出现的 `_S5_` 不是对象定义
+    assert_eq!(find_s5_sleep_types(b"\x14_S5_\x12\x06\x04\x00\x00"), None);
+}
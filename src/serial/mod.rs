@@ -0,0 +1,70 @@
+// 串口(16550 UART)输出
+// QEMU可以通过 `-serial stdio` 把COM1重定向到宿主机的标准输出，这样启动日志、panic信息和测试结果都能在宿主机上看到
+
+// 引入Rust的格式化模块，用于格式化输出
+use core::fmt;
+use lazy_static::lazy_static;
+use spin::Mutex;
+// `uart_16550` crate 封装了16550 UART的寄存器初始化和收发逻辑
+use uart_16550::SerialPort;
+use x86_64::instructions::interrupts;
+
+// COM1的标准I/O端口基地址。16550 UART的各个寄存器依次映射在基地址之后的8个端口上
+const COM1_PORT: u16 = 0x3F8;
+
+// 和 `vga_buffer` 的控制台输出器一样使用 `lazy_static` 在第一次使用时才初始化串口，并用自旋锁保证同步访问
+lazy_static! {
+    pub static ref SERIAL1: Mutex<SerialPort> = {
+        // 创建串口实例需要直接访问I/O端口，传入错误的端口号可能导致未定义行为，所以需要unsafe
+        let mut serial_port = unsafe { SerialPort::new(COM1_PORT) };
+        // 设置波特率、数据位、停止位并开启FIFO
+        serial_port.init();
+        Mutex::new(serial_port)
+    };
+}
+
+// 定义函数 `_print` 向串口输出格式化文本，对应 `vga_buffer::_print`
+// - 同样在关闭中断的情况下持有锁，避免中断处理函数中再次打印时发生死锁
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    use core::fmt::Write;
+
+    interrupts::without_interrupts(|| {
+        SERIAL1
+            .lock()
+            .write_fmt(args)
+            .expect("Printing to serial failed");
+    });
+}
+
+// 在panic处理函数中向串口输出，不等待锁
+// 其他地方总是关闭中断后才持有这把锁，所以panic时锁仍被占用，说明持有者就是发生panic的这段代码，它不会再释放，直接强制解锁
+pub fn panic_print(args: fmt::Arguments) {
+    use core::fmt::Write;
+
+    interrupts::disable();
+    if SERIAL1.is_locked() {
+        unsafe { SERIAL1.force_unlock() };
+    }
+    let _ = SERIAL1.lock().write_fmt(args);
+}
+
+// 通过串口向宿主机打印，用法与 `print!` 相同
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => ($crate::serial::_print(format_args!($($arg)*)));
+}
+
+// 通过串口向宿主机打印并追加换行符，用法与 `println!` 相同
+#[macro_export]
+macro_rules! serial_println {
+    () => ($crate::serial_print!("\n"));
+    ($fmt:expr) => ($crate::serial_print!(concat!($fmt, "\n")));
+    ($fmt:expr, $($arg:tt)*) => ($crate::serial_print!(
+        concat!($fmt, "\n"), $($arg)*));
+}
+
+#[test_case]
+fn test_serial_println() {
+    serial_println!("test_serial_println output");
+}
@@ -0,0 +1,165 @@
+// 基于waker的执行器
+// 只有被唤醒的任务才会被放进就绪队列并轮询。就绪队列为空时CPU执行 `hlt` 进入休眠，直到下一个中断到来，
+// 中断处理函数唤醒等待该事件的任务后，执行器继续运行
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::task::{Context, Poll, Waker};
+// 固定容量的无锁队列。中断处理函数中可能会唤醒任务，不能在那里进行堆分配或等待锁，所以使用预先分配好空间的无锁队列
+use crossbeam_queue::ArrayQueue;
+
+use super::{Task, TaskId};
+
+// 就绪队列的容量，即同时处于就绪状态的任务数上限
+const TASK_QUEUE_CAPACITY: usize = 100;
+
+pub struct Executor {
+    // 所有未完成的任务
+    tasks: BTreeMap<TaskId, Task>,
+    // 就绪任务的ID。执行器和所有waker共享这个队列
+    task_queue: Arc<ArrayQueue<TaskId>>,
+    // 每个任务的waker缓存，避免每次轮询都重新创建
+    waker_cache: BTreeMap<TaskId, Waker>,
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Executor {
+            tasks: BTreeMap::new(),
+            task_queue: Arc::new(ArrayQueue::new(TASK_QUEUE_CAPACITY)),
+            waker_cache: BTreeMap::new(),
+        }
+    }
+
+    // 添加一个新任务，新任务会被立即放进就绪队列
+    pub fn spawn(&mut self, task: Task) {
+        let task_id = task.id;
+        if self.tasks.insert(task.id, task).is_some() {
+            panic!("task with same ID already in tasks");
+        }
+        self.task_queue.push(task_id).expect("queue full");
+    }
+
+    // 运行执行器，永不返回
+    pub fn run(&mut self) -> ! {
+        loop {
+            self.run_ready_tasks();
+            self.sleep_if_idle();
+        }
+    }
+
+    // 轮询就绪队列中的所有任务
+    fn run_ready_tasks(&mut self) {
+        // 解构 `self`，避免闭包借用整个 `self` 导致的借用冲突
+        let Self {
+            tasks,
+            task_queue,
+            waker_cache,
+        } = self;
+
+        while let Some(task_id) = task_queue.pop() {
+            let task = match tasks.get_mut(&task_id) {
+                Some(task) => task,
+                None => continue, // 任务已经完成，但仍被重复唤醒
+            };
+            let waker = waker_cache
+                .entry(task_id)
+                .or_insert_with(|| TaskWaker::waker(task_id, task_queue.clone()));
+            let mut context = Context::from_waker(waker);
+            match task.poll(&mut context) {
+                Poll::Ready(()) => {
+                    // 任务已完成，移除任务和它的waker
+                    tasks.remove(&task_id);
+                    waker_cache.remove(&task_id);
+                }
+                Poll::Pending => {}
+            }
+        }
+    }
+
+    // 就绪队列为空时休眠
+    // 检查队列和执行 `hlt` 之间如果恰好发生中断并唤醒了任务，这次唤醒就会被错过，CPU一直睡到下一个中断。
+    // 所以先关闭中断再检查，然后用 `enable_and_hlt` 原子地开启中断并休眠
+    fn sleep_if_idle(&self) {
+        use x86_64::instructions::interrupts::{self, enable_and_hlt};
+
+        interrupts::disable();
+        if self.task_queue.is_empty() {
+            enable_and_hlt();
+        } else {
+            interrupts::enable();
+        }
+    }
+}
+
+// 任务的waker：唤醒时把任务ID放回就绪队列
+struct TaskWaker {
+    task_id: TaskId,
+    task_queue: Arc<ArrayQueue<TaskId>>,
+}
+
+impl TaskWaker {
+    // 创建任务的waker
+    fn waker(task_id: TaskId, task_queue: Arc<ArrayQueue<TaskId>>) -> Waker {
+        Waker::from(Arc::new(TaskWaker {
+            task_id,
+            task_queue,
+        }))
+    }
+
+    fn wake_task(&self) {
+        self.task_queue.push(self.task_id).expect("task_queue full");
+    }
+}
+
+// 实现 `Wake` trait 后可以通过 `Waker::from` 把 `Arc<TaskWaker>` 转换成 `Waker`，不需要手写 `RawWakerVTable`
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_task();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wake_task();
+    }
+}
+
+#[test_case]
+fn test_executor_polls_woken_task_again() {
+    use core::future::Future;
+    use core::pin::Pin;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static POLLS: AtomicUsize = AtomicUsize::new(0);
+
+    // 第一次轮询时唤醒自己并返回 `Pending`，第二次轮询时完成
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+            POLLS.fetch_add(1, Ordering::SeqCst);
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    let mut executor = Executor::new();
+    executor.spawn(Task::new(YieldOnce(false)));
+    executor.run_ready_tasks();
+    assert_eq!(POLLS.load(Ordering::SeqCst), 2);
+    assert!(executor.tasks.is_empty());
+    assert!(executor.waker_cache.is_empty());
+}
@@ -0,0 +1,293 @@
+// 异步键盘输入
+// 键盘中断处理函数只负责从0x60端口读出扫描码并放进一个固定容量的无锁队列，然后立即返回。
+// 解码扫描码、处理按键等耗时的工作交给异步任务，通过实现了 `Stream` 的 `ScancodeStream` 逐个取出扫描码。
+// 这样中断处理函数里不再持有任何锁，也不会因为打印输出而长时间关闭中断。
+// 解码出的按键放进当前活动虚拟控制台的输入队列，运行在各个控制台上的任务通过 `KeyStream` 读取自己的输入
+
+use alloc::collections::VecDeque;
+use conquer_once::spin::OnceCell;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll};
+use crossbeam_queue::ArrayQueue;
+use spin::Mutex;
+// `AtomicWaker` 可以在中断处理函数中安全地唤醒另一个上下文中注册的waker，不需要加锁
+use futures_util::stream::{Stream, StreamExt};
+use futures_util::task::AtomicWaker;
+use pc_keyboard::{layouts, DecodedKey, HandleControl, KeyCode, KeyState, Keyboard, ScancodeSet1};
+
+use x86_64::instructions::port::Port;
+
+use crate::interrupts::{self, InterruptIndex};
+use crate::vga_buffer::{self, CONSOLE_COUNT};
+use crate::{console_print, println};
+
+// 扫描码队列的容量。异步任务来不及处理时最多缓存这么多扫描码，再多的会被丢弃
+const SCANCODE_QUEUE_CAPACITY: usize = 100;
+
+// 扫描码队列
+// 不能使用 `lazy_static`：第一次访问时才会初始化，而第一次访问可能发生在中断处理函数中，初始化时的堆分配可能导致死锁。
+// 使用 `OnceCell` 由 `ScancodeStream::new` 显式初始化，中断处理函数只在它已经初始化后才使用
+static SCANCODE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
+// 等待扫描码的任务的waker
+static WAKER: AtomicWaker = AtomicWaker::new();
+// 因队列已满而丢弃的扫描码数量
+static DROPPED_SCANCODES: AtomicU64 = AtomicU64::new(0);
+
+// 每个虚拟控制台的输入队列最多缓存的按键数，没有任务读取时多出来的旧按键会被丢弃
+const INPUT_QUEUE_CAPACITY: usize = 64;
+
+// 每个虚拟控制台的输入队列和等待输入的任务的waker
+// 只在异步任务中访问，不会被中断处理函数使用，所以可以用普通的锁和堆上的队列
+static INPUT: [Mutex<VecDeque<DecodedKey>>; CONSOLE_COUNT] = [const { Mutex::new(VecDeque::new()) }; CONSOLE_COUNT];
+static INPUT_WAKERS: [AtomicWaker; CONSOLE_COUNT] = [const { AtomicWaker::new() }; CONSOLE_COUNT];
+
+// 键盘(IRQ1)的中断处理函数，由 `init` 注册
+// 只读出扫描码交给队列，解码和处理由异步任务完成，尽量缩短中断处理的时间。EOI由中断分发函数发送
+fn interrupt_handler() {
+    // 0x60是标准PS/2键盘的数据端口。必须读出扫描码，否则键盘控制器不会发送下一个中断
+    let mut port = Port::new(0x60);
+    let scancode: u8 = unsafe { port.read() };
+    add_scancode(scancode);
+}
+
+// 注册键盘中断的处理函数
+pub fn init() {
+    interrupts::register_irq(InterruptIndex::Keyboard.irq(), interrupt_handler)
+        .expect("keyboard IRQ already registered");
+}
+
+// 由键盘中断处理函数调用，把扫描码放进队列并唤醒等待的任务
+// 不能阻塞、不能进行堆分配，也不能打印：被中断的代码可能正持有控制台的锁。
+// 队列已满时丢弃扫描码并计数，由键盘任务在下次取出扫描码时打印警告；还没有创建 `ScancodeStream` 时没有任务处理按键，直接忽略
+pub(crate) fn add_scancode(scancode: u8) {
+    if let Ok(queue) = SCANCODE_QUEUE.try_get() {
+        if queue.push(scancode).is_err() {
+            DROPPED_SCANCODES.fetch_add(1, Ordering::Relaxed);
+        } else {
+            WAKER.wake();
+        }
+    }
+}
+
+// 因队列已满而丢弃的扫描码数量
+pub fn dropped_scancodes() -> u64 {
+    DROPPED_SCANCODES.load(Ordering::Relaxed)
+}
+
+// 扫描码流，每次产生一个从键盘中断收到的扫描码
+// 队列只能有一个消费者，所以只允许创建一个实例
+pub struct ScancodeStream {
+    // 防止在模块外部不通过 `new` 直接构造
+    _private: (),
+}
+
+impl ScancodeStream {
+    // 只能调用一次，所以不提供 `Default`
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        SCANCODE_QUEUE
+            .try_init_once(|| ArrayQueue::new(SCANCODE_QUEUE_CAPACITY))
+            .expect("ScancodeStream::new should only be called once");
+        ScancodeStream { _private: () }
+    }
+}
+
+impl Stream for ScancodeStream {
+    type Item = u8;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<u8>> {
+        let queue = SCANCODE_QUEUE
+            .try_get()
+            .expect("scancode queue not initialized");
+
+        // 快速路径：队列中已经有扫描码时不需要注册waker
+        if let Some(scancode) = queue.pop() {
+            return Poll::Ready(Some(scancode));
+        }
+
+        // 先注册waker再检查一次队列。如果在第一次检查和注册之间有扫描码到达，中断处理函数唤醒的是旧的waker，
+        // 这次检查能保证不会错过它
+        WAKER.register(cx.waker());
+        match queue.pop() {
+            Some(scancode) => {
+                WAKER.take();
+                Poll::Ready(Some(scancode))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+// 把按键放进第 `console` 个控制台的输入队列并唤醒等待的任务
+fn push_key(console: usize, key: DecodedKey) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut queue = INPUT[console].lock();
+        if queue.len() == INPUT_QUEUE_CAPACITY {
+            queue.pop_front();
+        }
+        queue.push_back(key);
+    });
+    INPUT_WAKERS[console].wake();
+}
+
+// 从第 `console` 个控制台的输入队列取出一个按键，没有输入时返回 `None`
+pub fn read_key(console: usize) -> Option<DecodedKey> {
+    x86_64::instructions::interrupts::without_interrupts(|| INPUT[console].lock().pop_front())
+}
+
+// 某个虚拟控制台的按键流，只有该控制台在前台时敲下的按键才会出现在流中
+pub struct KeyStream {
+    console: usize,
+}
+
+impl KeyStream {
+    pub fn new(console: usize) -> Self {
+        assert!(console < CONSOLE_COUNT, "console index {} out of range", console);
+        KeyStream { console }
+    }
+}
+
+impl Stream for KeyStream {
+    type Item = DecodedKey;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<DecodedKey>> {
+        if let Some(key) = read_key(self.console) {
+            return Poll::Ready(Some(key));
+        }
+
+        // 和 `ScancodeStream` 一样，注册waker之后再检查一次，避免错过期间到达的按键
+        INPUT_WAKERS[self.console].register(cx.waker());
+        match read_key(self.console) {
+            Some(key) => {
+                INPUT_WAKERS[self.console].take();
+                Poll::Ready(Some(key))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+// 键盘处理任务：不断从扫描码流中取出扫描码，解码后交给当前活动控制台，并在该控制台上回显
+// Shift+PageUp/PageDown用来翻看屏幕的回滚历史，Alt+F1到Alt+F6用来切换虚拟控制台，都不会交给控制台
+pub async fn print_keypresses() {
+    let mut scancodes = ScancodeStream::new();
+    // 支持美国104键布局和扫描码集1，忽略控制字符(例如Ctrl组合按键)
+    let mut keyboard = Keyboard::new(layouts::Us104Key, ScancodeSet1, HandleControl::Ignore);
+    // 左右Shift、Alt键是否按下。`Keyboard` 不对外提供修饰键的状态，需要自己记录
+    let (mut left_shift, mut right_shift) = (false, false);
+    let (mut left_alt, mut right_alt) = (false, false);
+    // 上次检查时已经丢弃的扫描码数量
+    let mut dropped = dropped_scancodes();
+
+    while let Some(scancode) = scancodes.next().await {
+        // 中断处理函数里不能打印，队列满时丢弃的扫描码在这里报告
+        let now_dropped = dropped_scancodes();
+        if now_dropped > dropped {
+            println!("WARNING: dropped {} scancodes", now_dropped - dropped);
+            dropped = now_dropped;
+        }
+        // 将扫描码添加到 `keyboard` 中并尝试解析出具体的按键事件。
+        // - 如果成功解析成Unicode字符，则直接打印该字符。
+        // - 如果是特殊按键，则打印其原始按键值的Debug表示形式。
+        if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
+            let down = key_event.state == KeyState::Down;
+            match key_event.code {
+                KeyCode::ShiftLeft => left_shift = down,
+                KeyCode::ShiftRight => right_shift = down,
+                KeyCode::AltLeft => left_alt = down,
+                KeyCode::AltRight => right_alt = down,
+                KeyCode::PageUp if down && (left_shift || right_shift) => {
+                    vga_buffer::scroll_up();
+                    continue;
+                }
+                KeyCode::PageDown if down && (left_shift || right_shift) => {
+                    vga_buffer::scroll_down();
+                    continue;
+                }
+                code if down && (left_alt || right_alt) => {
+                    if let Some(console) = function_key_console(code) {
+                        vga_buffer::switch_console(console);
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            if let Some(key) = keyboard.process_keyevent(key_event) {
+                let console = vga_buffer::active_console();
+                push_key(console, key);
+                match key {
+                    DecodedKey::Unicode(character) => console_print!(console, "{}", character),
+                    DecodedKey::RawKey(key) => console_print!(console, "{:?}", key),
+                }
+            }
+        }
+    }
+}
+
+// F1到F6对应的控制台下标
+fn function_key_console(code: KeyCode) -> Option<usize> {
+    match code {
+        KeyCode::F1 => Some(0),
+        KeyCode::F2 => Some(1),
+        KeyCode::F3 => Some(2),
+        KeyCode::F4 => Some(3),
+        KeyCode::F5 => Some(4),
+        KeyCode::F6 => Some(5),
+        _ => None,
+    }
+}
+
+#[test_case]
+fn test_scancode_stream_yields_queued_scancodes() {
+    use futures_util::task::noop_waker_ref;
+
+    let mut stream = ScancodeStream::new();
+    let mut context = Context::from_waker(noop_waker_ref());
+
+    // 队列为空时返回 `Pending`
+    assert_eq!(Pin::new(&mut stream).poll_next(&mut context), Poll::Pending);
+
+    // 按到达顺序取出扫描码
+    add_scancode(0x1e);
+    add_scancode(0x9e);
+    assert_eq!(Pin::new(&mut stream).poll_next(&mut context), Poll::Ready(Some(0x1e)));
+    assert_eq!(Pin::new(&mut stream).poll_next(&mut context), Poll::Ready(Some(0x9e)));
+    assert_eq!(Pin::new(&mut stream).poll_next(&mut context), Poll::Pending);
+}
+
+#[test_case]
+fn test_full_scancode_queue_counts_dropped() {
+    // 队列已经由上一个测试创建的 `ScancodeStream` 初始化
+    let queue = SCANCODE_QUEUE.try_get().expect("scancode queue not initialized");
+    while queue.pop().is_some() {}
+
+    for _ in 0..SCANCODE_QUEUE_CAPACITY {
+        add_scancode(0x1e);
+    }
+    let before = dropped_scancodes();
+    add_scancode(0x1e);
+    assert_eq!(dropped_scancodes(), before + 1);
+    assert_eq!(queue.len(), SCANCODE_QUEUE_CAPACITY);
+
+    while queue.pop().is_some() {}
+}
+
+#[test_case]
+fn test_key_stream_reads_own_console() {
+    use futures_util::task::noop_waker_ref;
+
+    let mut stream = KeyStream::new(2);
+    let mut context = Context::from_waker(noop_waker_ref());
+
+    // 其他控制台的输入不会出现在这个流中
+    push_key(3, DecodedKey::Unicode('x'));
+    assert_eq!(Pin::new(&mut stream).poll_next(&mut context), Poll::Pending);
+
+    push_key(2, DecodedKey::Unicode('a'));
+    push_key(2, DecodedKey::Unicode('b'));
+    assert_eq!(Pin::new(&mut stream).poll_next(&mut context), Poll::Ready(Some(DecodedKey::Unicode('a'))));
+    assert_eq!(Pin::new(&mut stream).poll_next(&mut context), Poll::Ready(Some(DecodedKey::Unicode('b'))));
+    assert_eq!(Pin::new(&mut stream).poll_next(&mut context), Poll::Pending);
+    assert_eq!(read_key(3), Some(DecodedKey::Unicode('x')));
+}
@@ -0,0 +1,47 @@
+// 协作式异步任务
+// 每个内核服务写成一个 `async fn`，编译器会把它转换成一个状态机(实现了 `Future` 的类型)。执行器轮询这些任务，
+// 任务在等待事件时返回 `Poll::Pending` 主动让出CPU，事件发生后通过 `Waker` 通知执行器重新轮询
+
+use alloc::boxed::Box;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll};
+
+pub mod executor;
+pub mod keyboard;
+pub mod simple_executor;
+
+// 任务的唯一标识，执行器用它在任务表和唤醒队列之间关联任务
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct TaskId(u64);
+
+impl TaskId {
+    fn new() -> Self {
+        // 全局递增计数器，保证每个任务的ID都不同。只需要原子性，不需要与其他内存操作同步，所以使用 `Relaxed`
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        TaskId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+// 一个异步任务
+// - `Box<dyn Future>`: 不同的 `async fn` 生成的类型各不相同，通过trait对象统一存储在堆上
+// - `Pin`: async状态机内部可能存在自引用，被轮询之后就不能再移动，`Pin<Box<_>>` 保证了这一点
+// - `Output = ()`: 任务只是为了执行副作用，结果没有意义
+pub struct Task {
+    id: TaskId,
+    future: Pin<Box<dyn Future<Output = ()>>>,
+}
+
+impl Task {
+    pub fn new(future: impl Future<Output = ()> + 'static) -> Task {
+        Task {
+            id: TaskId::new(),
+            future: Box::pin(future),
+        }
+    }
+
+    fn poll(&mut self, context: &mut Context) -> Poll<()> {
+        self.future.as_mut().poll(context)
+    }
+}
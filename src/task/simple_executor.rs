@@ -0,0 +1,76 @@
+// 简单执行器
+// 不使用真正的 `Waker`，而是把所有任务放在一个先进先出队列里轮流轮询，直到全部完成。
+// 任务即使在等待事件也会被不停地轮询，浪费CPU，只适合调试和测试
+
+use alloc::collections::VecDeque;
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use super::Task;
+
+pub struct SimpleExecutor {
+    task_queue: VecDeque<Task>,
+}
+
+impl Default for SimpleExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimpleExecutor {
+    pub fn new() -> SimpleExecutor {
+        SimpleExecutor {
+            task_queue: VecDeque::new(),
+        }
+    }
+
+    pub fn spawn(&mut self, task: Task) {
+        self.task_queue.push_back(task)
+    }
+
+    // 轮询所有任务直到队列为空。未完成的任务重新放回队尾
+    pub fn run(&mut self) {
+        while let Some(mut task) = self.task_queue.pop_front() {
+            let waker = dummy_waker();
+            let mut context = Context::from_waker(&waker);
+            match task.poll(&mut context) {
+                Poll::Ready(()) => {} // 任务已完成
+                Poll::Pending => self.task_queue.push_back(task),
+            }
+        }
+    }
+}
+
+// 创建一个什么都不做的 `RawWaker`
+// `RawWakerVTable` 中的函数在waker被克隆、唤醒和丢弃时调用，这里全部为空操作，克隆时返回一个新的空waker
+fn dummy_raw_waker() -> RawWaker {
+    fn no_op(_: *const ()) {}
+    fn clone(_: *const ()) -> RawWaker {
+        dummy_raw_waker()
+    }
+
+    let vtable = &RawWakerVTable::new(clone, no_op, no_op, no_op);
+    RawWaker::new(core::ptr::null(), vtable)
+}
+
+fn dummy_waker() -> Waker {
+    // 空waker不访问任何数据，满足 `RawWaker` 的约定，所以是安全的
+    unsafe { Waker::from_raw(dummy_raw_waker()) }
+}
+
+#[test_case]
+fn test_simple_executor_runs_all_tasks() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    async fn increment() {
+        COUNTER.fetch_add(1, Ordering::SeqCst);
+    }
+
+    let mut executor = SimpleExecutor::new();
+    executor.spawn(Task::new(increment()));
+    executor.spawn(Task::new(increment()));
+    executor.run();
+    assert_eq!(COUNTER.load(Ordering::SeqCst), 2);
+}
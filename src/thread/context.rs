@@ -0,0 +1,120 @@
+// 线程上下文切换
+// `extern "x86-interrupt"` 函数只能拿到CPU自动压入的中断栈帧，无法访问被中断代码的通用寄存器，也不能在返回时换成另一个线程的栈。
+// 所以定时器中断和主动让出CPU的中断使用汇编编写的入口：
+// 1. 把全部15个通用寄存器压栈，与CPU压入的中断栈帧一起构成 `SavedContext`；
+// 2. 把此时的栈指针作为参数调用Rust函数，由调度器保存它并返回下一个线程的栈指针；
+// 3. 切换到返回的栈上，弹出那个线程的寄存器，最后用 `iretq` 恢复它的RIP、RFLAGS和栈。
+
+use core::arch::{asm, global_asm};
+use core::mem;
+use x86_64::VirtAddr;
+
+// 主动让出CPU使用的软件中断向量号。在PIC映射的硬件中断(32..48)之外，避免与任何IRQ冲突
+pub const YIELD_INTERRUPT_VECTOR: u8 = 0x81;
+
+// 被切换出去的线程保存在自己栈上的全部寄存器，从低地址到高地址排列
+// 前15项由汇编入口压栈(最后压入的 `r15` 在最低处)，后5项是CPU在中断时自动压入的中断栈帧
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct SavedContext {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rbp: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rdx: u64,
+    pub rcx: u64,
+    pub rbx: u64,
+    pub rax: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+}
+
+// RFLAGS中的中断允许位(IF)和必须为1的保留位1。新线程一开始就允许中断，这样才能被定时器抢占
+const INITIAL_RFLAGS: u64 = 0x202;
+
+// 在新线程的栈顶构造一份初始上下文，返回保存它的栈指针
+// 第一次切换到该线程时，汇编入口会把这份上下文当作被中断时保存的寄存器恢复，`iretq` 之后从 `entry(arg)` 开始执行
+/// # Safety
+///
+/// 调用者必须保证 `stack_top` 之下有足够的可写空间，并且 `entry` 永远不会返回
+pub unsafe fn init_stack(stack_top: VirtAddr, entry: extern "C" fn(u64) -> !, arg: u64) -> u64 {
+    use x86_64::instructions::segmentation::{Segment, CS, SS};
+
+    // System V调用约定要求函数入口处 `rsp + 8` 是16字节对齐的(相当于刚刚执行完 `call`)
+    let thread_rsp = stack_top.align_down(16u64) - 8u64;
+    let context_ptr = (thread_rsp - mem::size_of::<SavedContext>() as u64).as_mut_ptr::<SavedContext>();
+    context_ptr.write(SavedContext {
+        // 第一个整数参数通过rdi传递
+        rdi: arg,
+        rip: entry as *const () as u64,
+        cs: u64::from(CS::get_reg().0),
+        rflags: INITIAL_RFLAGS,
+        rsp: thread_rsp.as_u64(),
+        ss: u64::from(SS::get_reg().0),
+        ..SavedContext::default()
+    });
+    context_ptr as u64
+}
+
+// 汇编入口
+// - `thread_timer_entry`: 定时器中断(IRQ0)的入口，调用 `interrupts::time_interrupt_handler`
+// - `thread_yield_entry`: `int YIELD_INTERRUPT_VECTOR` 的入口，调用 `thread::yield_interrupt_handler`
+// 两者共用后半部分的恢复代码。进入时CPU已经关闭了中断(中断门)，直到 `iretq` 恢复RFLAGS
+// 中断栈帧(5项)加15个寄存器共160字节，CPU在压入中断栈帧前会把栈指针16字节对齐，所以调用Rust函数时栈满足对齐要求
+global_asm!(
+    ".global thread_timer_entry",
+    "thread_timer_entry:",
+    "push rax", "push rbx", "push rcx", "push rdx", "push rsi", "push rdi", "push rbp",
+    "push r8", "push r9", "push r10", "push r11", "push r12", "push r13", "push r14", "push r15",
+    "mov rdi, rsp",
+    "call {timer}",
+    "jmp thread_restore",
+    "",
+    ".global thread_yield_entry",
+    "thread_yield_entry:",
+    "push rax", "push rbx", "push rcx", "push rdx", "push rsi", "push rdi", "push rbp",
+    "push r8", "push r9", "push r10", "push r11", "push r12", "push r13", "push r14", "push r15",
+    "mov rdi, rsp",
+    "call {yield_}",
+    "",
+    // rax中是调度器返回的下一个线程的栈指针
+    "thread_restore:",
+    "mov rsp, rax",
+    "pop r15", "pop r14", "pop r13", "pop r12", "pop r11", "pop r10", "pop r9", "pop r8",
+    "pop rbp", "pop rdi", "pop rsi", "pop rdx", "pop rcx", "pop rbx", "pop rax",
+    "iretq",
+    timer = sym crate::interrupts::time_interrupt_handler,
+    yield_ = sym super::yield_interrupt_handler,
+);
+
+extern "C" {
+    fn thread_timer_entry();
+    fn thread_yield_entry();
+}
+
+// 定时器中断入口的地址，用于设置IDT
+pub fn timer_entry_addr() -> VirtAddr {
+    VirtAddr::new(thread_timer_entry as *const () as u64)
+}
+
+// 主动让出CPU中断入口的地址，用于设置IDT
+pub fn yield_entry_addr() -> VirtAddr {
+    VirtAddr::new(thread_yield_entry as *const () as u64)
+}
+
+// 触发让出CPU的软件中断
+pub fn trigger_yield() {
+    unsafe {
+        asm!("int {vector}", vector = const YIELD_INTERRUPT_VECTOR);
+    }
+}
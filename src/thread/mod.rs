@@ -0,0 +1,500 @@
+// 抢占式内核线程
+// 每个线程有自己的栈。定时器中断时保存当前线程的全部寄存器，由调度器选出下一个就绪线程并切换到它的栈上，
+// 所以即使某个线程一直在做耗时的计算，键盘和控制台等其他工作也能按时得到CPU
+//
+// 启动时执行 `kernel_main` 的那个执行流被登记为引导线程，它没有额外分配的栈。另有一个空闲线程，在没有任何就绪线程时运行 `hlt`
+// 这里只负责线程的创建、阻塞、唤醒和切换，下一个运行哪个线程由 `scheduler` 中启动时选择的调度策略决定
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+use x86_64::VirtAddr;
+
+use crate::time;
+use scheduler::{Priority, Scheduler, SchedulerPolicy, DEFAULT_PRIORITY};
+
+pub mod context;
+pub mod scheduler;
+
+// 每个线程的栈大小(16 KiB)
+const STACK_SIZE: usize = 4096 * 4;
+
+// 线程的唯一标识
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ThreadId(u64);
+
+impl ThreadId {
+    fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        ThreadId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+// 线程状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+    // 在就绪队列中等待运行
+    Ready,
+    // 正在运行
+    Running,
+    // 正在等待其他线程结束，不会被调度
+    Blocked,
+    // 已经结束，等待回收栈
+    Finished,
+}
+
+// 线程的调度统计，时间以定时器中断次数(tick)为单位，用于比较不同调度策略的表现
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThreadStats {
+    // 在CPU上运行的tick数
+    pub run_ticks: u64,
+    // 处于就绪状态、等待被调度的tick数
+    pub wait_ticks: u64,
+    // 从其他线程切换到该线程的次数
+    pub switches: u64,
+}
+
+struct Thread {
+    state: ThreadState,
+    priority: Priority,
+    // 线程被切换出去时保存的栈指针，指向栈上的 `context::SavedContext`
+    rsp: u64,
+    // 线程的栈。引导线程使用bootloader提供的栈，这里为 `None`
+    // 栈只在线程结束并且不再运行之后才会被释放。这里只是持有栈的所有权，不会读取它
+    #[allow(dead_code)]
+    stack: Option<Box<[u8]>>,
+    // 调用了 `join` 等待该线程结束的线程
+    joiners: Vec<ThreadId>,
+    // `JoinHandle` 已被丢弃，没有人会再来取统计信息，结束后可以直接回收
+    detached: bool,
+    stats: ThreadStats,
+    // 最近一次进入就绪状态时的tick数，用于计算等待时间
+    ready_since: u64,
+}
+
+impl Thread {
+    // 创建一个新线程，开始执行时调用 `thread_entry(entry_arg)`
+    fn new(entry_arg: u64, priority: Priority) -> Self {
+        let mut stack = vec![0u8; STACK_SIZE].into_boxed_slice();
+        let stack_top = VirtAddr::from_ptr(stack.as_mut_ptr()) + STACK_SIZE;
+        // 栈是新分配的，大小足够放下初始上下文，`thread_entry` 永不返回
+        let rsp = unsafe { context::init_stack(stack_top, thread_entry, entry_arg) };
+        Thread {
+            state: ThreadState::Ready,
+            priority,
+            rsp,
+            stack: Some(stack),
+            joiners: Vec::new(),
+            detached: false,
+            stats: ThreadStats::default(),
+            ready_since: time::ticks(),
+        }
+    }
+}
+
+struct ThreadManager {
+    threads: BTreeMap<ThreadId, Thread>,
+    // 启动时选择的调度策略，保存所有就绪线程(空闲线程除外)
+    scheduler: Box<dyn Scheduler>,
+    policy: SchedulerPolicy,
+    current: ThreadId,
+    idle: ThreadId,
+}
+
+impl ThreadManager {
+    // 把线程标记为就绪并交给调度策略
+    // 空闲线程只在没有其他就绪线程时运行，不交给调度策略
+    fn make_ready(&mut self, id: ThreadId) {
+        let thread = self.threads.get_mut(&id).expect("thread missing");
+        thread.state = ThreadState::Ready;
+        thread.ready_since = time::ticks();
+        if id != self.idle {
+            self.scheduler.enqueue(id, thread.priority);
+        }
+    }
+
+    // 保存当前线程的栈指针，选出下一个线程，返回它的栈指针
+    // 当前线程仍处于 `Running` 状态时重新变为就绪；已经阻塞或结束的线程则不再交给调度策略
+    fn switch(&mut self, current_rsp: u64) -> u64 {
+        let current_id = self.current;
+        let current = self.threads.get_mut(&current_id).expect("current thread missing");
+        current.rsp = current_rsp;
+        if current.state == ThreadState::Running {
+            self.make_ready(current_id);
+        }
+
+        let next_id = self.scheduler.pick_next().unwrap_or(self.idle);
+        let ticks = time::ticks();
+        let next = self.threads.get_mut(&next_id).expect("next thread missing");
+        next.stats.wait_ticks += ticks - next.ready_since;
+        if next_id != current_id {
+            next.stats.switches += 1;
+        }
+        next.state = ThreadState::Running;
+        self.current = next_id;
+        next.rsp
+    }
+
+    // 定时器中断：记账，并由调度策略决定是否抢占当前线程
+    fn tick(&mut self, current_rsp: u64) -> u64 {
+        let current_id = self.current;
+        let current = self.threads.get_mut(&current_id).expect("current thread missing");
+        current.stats.run_ticks += 1;
+        // 空闲线程总是让给新就绪的线程；已经阻塞或结束、还没来得及让出的线程直接切换
+        let preempt = current_id == self.idle
+            || current.state != ThreadState::Running
+            || self.scheduler.tick(current_id);
+        if preempt {
+            self.switch(current_rsp)
+        } else {
+            current_rsp
+        }
+    }
+
+    // 唤醒被阻塞的线程
+    fn wake(&mut self, id: ThreadId) {
+        if let Some(thread) = self.threads.get(&id) {
+            if thread.state == ThreadState::Blocked {
+                self.make_ready(id);
+            }
+        }
+    }
+
+    // 回收已经结束并且不会再被 `join` 的线程。当前线程还在使用自己的栈，所以即使它已结束也要等到下一次
+    fn reap(&mut self) {
+        let current = self.current;
+        self.threads.retain(|&id, thread| {
+            id == current || thread.state != ThreadState::Finished || !thread.detached
+        });
+    }
+}
+
+// 全局线程管理器。在 `init` 之前为 `None`，此时定时器中断不会切换线程
+// 中断处理函数也会访问它，所以在线程上下文中必须关闭中断后才能加锁，否则中断处理函数可能在同一个CPU上等待这把锁而死锁
+static MANAGER: Mutex<Option<ThreadManager>> = Mutex::new(None);
+
+// 关闭中断并获取线程管理器
+fn with_manager<R>(f: impl FnOnce(&mut ThreadManager) -> R) -> R {
+    interrupts::without_interrupts(|| {
+        let mut manager = MANAGER.lock();
+        f(manager.as_mut().expect("thread scheduler not initialized"))
+    })
+}
+
+// 初始化线程调度：把当前执行流登记为引导线程，创建空闲线程，并使用 `policy` 调度之后创建的所有线程
+// 需要在 `allocator::init_heap` 之后调用一次
+pub fn init(policy: SchedulerPolicy) {
+    let boot_id = ThreadId::new();
+    let boot = Thread {
+        state: ThreadState::Running,
+        priority: DEFAULT_PRIORITY,
+        // 第一次被切换出去时才会保存真正的值
+        rsp: 0,
+        stack: None,
+        joiners: Vec::new(),
+        detached: true,
+        stats: ThreadStats::default(),
+        ready_since: 0,
+    };
+
+    let idle_id = ThreadId::new();
+    let idle_main: ThreadMain = Box::new(idle_loop);
+    let mut idle = Thread::new(Box::into_raw(Box::new(idle_main)) as u64, DEFAULT_PRIORITY);
+    idle.detached = true;
+
+    let mut threads = BTreeMap::new();
+    threads.insert(boot_id, boot);
+    threads.insert(idle_id, idle);
+
+    // 引导线程没有经过 `spawn`，也要让调度策略为它预留空间
+    let mut scheduler = policy.build();
+    scheduler.reserve(boot_id, DEFAULT_PRIORITY, threads.len());
+
+    interrupts::without_interrupts(|| {
+        *MANAGER.lock() = Some(ThreadManager {
+            threads,
+            scheduler,
+            policy,
+            current: boot_id,
+            idle: idle_id,
+        });
+    });
+}
+
+// 空闲线程：没有其他就绪线程时休眠等待中断
+fn idle_loop() {
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+// 线程的入口函数，通过 `Box<Box<dyn FnOnce>>` 的裸指针传递，内层的Box是胖指针，外层Box把它变成一个可以放进寄存器的普通指针
+type ThreadMain = Box<dyn FnOnce() + Send + 'static>;
+
+// 所有新线程开始执行的地方：取出入口函数并调用，返回后结束线程
+extern "C" fn thread_entry(arg: u64) -> ! {
+    let main = unsafe { Box::from_raw(arg as *mut ThreadMain) };
+    main();
+    exit();
+}
+
+// 定时器中断时调用，返回下一个要运行的线程的栈指针
+// 调度器尚未初始化时返回原来的栈指针，即继续运行被中断的代码
+pub(crate) fn on_timer_tick(current_rsp: u64) -> u64 {
+    match MANAGER.lock().as_mut() {
+        Some(manager) => manager.tick(current_rsp),
+        None => current_rsp,
+    }
+}
+
+// 主动让出CPU的中断处理函数，由 `context` 中的汇编入口调用
+// 与定时器中断不同，不需要询问调度策略，总是切换到下一个线程
+extern "C" fn yield_interrupt_handler(current_rsp: u64) -> u64 {
+    match MANAGER.lock().as_mut() {
+        Some(manager) => manager.switch(current_rsp),
+        None => current_rsp,
+    }
+}
+
+// 等待线程结束的句柄
+// 不调用 `join` 直接丢弃句柄时，线程继续运行，结束后由系统自动回收
+#[derive(Debug)]
+pub struct JoinHandle {
+    id: ThreadId,
+}
+
+impl JoinHandle {
+    pub fn id(&self) -> ThreadId {
+        self.id
+    }
+
+    // 阻塞当前线程直到目标线程结束，返回目标线程的调度统计
+    pub fn join(self) -> ThreadStats {
+        join(self.id)
+    }
+}
+
+impl Drop for JoinHandle {
+    fn drop(&mut self) {
+        let id = self.id;
+        with_manager(|manager| {
+            // 已经被 `join` 回收的线程不在表中
+            if let Some(thread) = manager.threads.get_mut(&id) {
+                thread.detached = true;
+            }
+            manager.reap();
+        });
+    }
+}
+
+// 创建一个默认优先级的新线程执行 `f`，新线程在下一次调度时开始运行
+pub fn spawn<F>(f: F) -> JoinHandle
+where
+    F: FnOnce() + Send + 'static,
+{
+    spawn_with_priority(DEFAULT_PRIORITY, f)
+}
+
+// 创建一个指定优先级的新线程执行 `f`
+// 优先级只影响 `Priority` 和 `FairShare` 两种调度策略
+pub fn spawn_with_priority<F>(priority: Priority, f: F) -> JoinHandle
+where
+    F: FnOnce() + Send + 'static,
+{
+    let id = ThreadId::new();
+    let main: ThreadMain = Box::new(f);
+    let thread = Thread::new(Box::into_raw(Box::new(main)) as u64, priority);
+
+    with_manager(|manager| {
+        manager.reap();
+        manager.threads.insert(id, thread);
+        let threads = manager.threads.len();
+        manager.scheduler.reserve(id, priority, threads);
+        manager.make_ready(id);
+    });
+
+    JoinHandle { id }
+}
+
+// 当前线程的ID
+pub fn current() -> ThreadId {
+    with_manager(|manager| manager.current)
+}
+
+// 启动时选择的调度策略
+pub fn policy() -> SchedulerPolicy {
+    with_manager(|manager| manager.policy)
+}
+
+// 当前调度策略的名称，例如 `round-robin`
+pub fn scheduler_name() -> &'static str {
+    with_manager(|manager| manager.scheduler.name())
+}
+
+// 线程 `id` 的调度统计，线程已被回收时返回 `None`
+pub fn stats(id: ThreadId) -> Option<ThreadStats> {
+    with_manager(|manager| manager.threads.get(&id).map(|thread| thread.stats))
+}
+
+// 所有线程(包括引导线程和空闲线程)的调度统计
+pub fn all_stats() -> Vec<(ThreadId, ThreadStats)> {
+    with_manager(|manager| {
+        manager
+            .threads
+            .iter()
+            .map(|(&id, thread)| (id, thread.stats))
+            .collect()
+    })
+}
+
+// 通过串口输出调度策略和所有线程的统计信息，便于在QEMU中比较不同策略
+pub fn print_stats() {
+    crate::serial_println!("scheduler: {} ({:?})", scheduler_name(), policy());
+    for (id, stats) in all_stats() {
+        crate::serial_println!(
+            "thread {}: run {} ticks, wait {} ticks, {} switches",
+            id.as_u64(),
+            stats.run_ticks,
+            stats.wait_ticks,
+            stats.switches
+        );
+    }
+}
+
+// 主动让出CPU，当前线程重新变为就绪
+// 调度器尚未初始化时直接返回
+pub fn yield_now() {
+    context::trigger_yield();
+}
+
+// 结束当前线程，唤醒所有等待它的线程
+pub fn exit() -> ! {
+    with_manager(|manager| {
+        let id = manager.current;
+        assert_ne!(id, manager.idle, "idle thread must not exit");
+        manager.scheduler.remove(id);
+        let thread = manager.threads.get_mut(&id).expect("current thread missing");
+        thread.state = ThreadState::Finished;
+        let joiners = core::mem::take(&mut thread.joiners);
+        for joiner in joiners {
+            manager.wake(joiner);
+        }
+    });
+    // 已结束的线程不会再被放回就绪队列，这次让出之后永远不会再回到这里
+    loop {
+        yield_now();
+    }
+}
+
+// 阻塞当前线程直到线程 `id` 结束，然后回收它并返回它的调度统计
+fn join(id: ThreadId) -> ThreadStats {
+    loop {
+        let finished = with_manager(|manager| {
+            let current = manager.current;
+            assert_ne!(id, current, "thread cannot join itself");
+            let target = manager.threads.get_mut(&id).expect("joined thread missing");
+            if target.state == ThreadState::Finished {
+                // 目标线程不再运行，可以释放它的栈
+                return manager.threads.remove(&id).map(|thread| thread.stats);
+            }
+            // 被提前唤醒后会再次进入这里，已经登记过的不要重复登记，否则会被唤醒两次
+            if !target.joiners.contains(&current) {
+                target.joiners.push(current);
+            }
+            // 标记为阻塞后让出CPU，目标线程结束时会唤醒当前线程
+            if let Some(thread) = manager.threads.get_mut(&current) {
+                thread.state = ThreadState::Blocked;
+            }
+            None
+        });
+        if let Some(stats) = finished {
+            return stats;
+        }
+        yield_now();
+    }
+}
+
+#[test_case]
+fn test_spawn_and_join() {
+    use core::sync::atomic::AtomicBool;
+
+    static RAN: AtomicBool = AtomicBool::new(false);
+
+    let handle = spawn(|| RAN.store(true, Ordering::SeqCst));
+    handle.join();
+    assert!(RAN.load(Ordering::SeqCst));
+}
+
+#[test_case]
+fn test_yield_now_interleaves_threads() {
+    use core::sync::atomic::AtomicUsize;
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let handles: Vec<JoinHandle> = (0..4)
+        .map(|_| {
+            spawn(|| {
+                for _ in 0..10 {
+                    COUNTER.fetch_add(1, Ordering::SeqCst);
+                    yield_now();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join();
+    }
+    assert_eq!(COUNTER.load(Ordering::SeqCst), 40);
+}
+
+#[test_case]
+fn test_exit_skips_rest_of_thread() {
+    use core::sync::atomic::AtomicBool;
+
+    static AFTER_EXIT: AtomicBool = AtomicBool::new(false);
+
+    spawn(|| {
+        exit();
+        #[allow(unreachable_code)]
+        AFTER_EXIT.store(true, Ordering::SeqCst);
+    })
+    .join();
+    assert!(!AFTER_EXIT.load(Ordering::SeqCst));
+}
+
+#[test_case]
+fn test_timer_preempts_busy_thread() {
+    use core::sync::atomic::AtomicBool;
+
+    static STARTED: AtomicBool = AtomicBool::new(false);
+
+    // 当前线程忙等而不主动让出，只有定时器中断抢占后新线程才能运行
+    let handle = spawn(|| STARTED.store(true, Ordering::SeqCst));
+    while !STARTED.load(Ordering::SeqCst) {
+        core::hint::spin_loop();
+    }
+    handle.join();
+}
+
+#[test_case]
+fn test_join_returns_stats() {
+    let handle = spawn(|| {
+        for _ in 0..3 {
+            yield_now();
+        }
+    });
+    let id = handle.id();
+    let joined = handle.join();
+    // 线程至少要被切换进来一次才能运行
+    assert!(joined.switches >= 1);
+    // join之后线程已被回收
+    assert_eq!(stats(id), None);
+}
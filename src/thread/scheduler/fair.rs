@@ -0,0 +1,181 @@
+// 公平调度(类似Linux的CFS)
+// 每个线程记录一个虚拟运行时间(vruntime)，实际运行一个tick时按权重增加：优先级越高权重越大，虚拟时间增长越慢，
+// 于是在相同的虚拟时间内能获得更多的CPU。调度时总是选择vruntime最小的就绪线程。
+// 新线程或长时间阻塞后被唤醒的线程，vruntime至少从当前的最小值开始，否则它会凭借很小的vruntime长时间独占CPU
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use super::{Priority, Scheduler, DEFAULT_PRIORITY};
+use crate::thread::ThreadId;
+
+// 默认优先级线程每个tick增加的虚拟时间
+const BASE_SLICE: u64 = 1024;
+// 当前线程的vruntime比最小的就绪线程多出这么多时才抢占，避免两个线程每个tick都来回切换
+const GRANULARITY: u64 = BASE_SLICE;
+
+// 优先级对应的权重：默认优先级为1024，每提高一级约增加25%，降低一级约减少20%
+fn weight(priority: Priority) -> u64 {
+    let mut weight = BASE_SLICE;
+    if priority >= DEFAULT_PRIORITY {
+        for _ in DEFAULT_PRIORITY..priority {
+            weight = weight * 5 / 4;
+        }
+    } else {
+        for _ in priority..DEFAULT_PRIORITY {
+            weight = weight * 4 / 5;
+        }
+    }
+    weight
+}
+
+struct Entity {
+    vruntime: u64,
+    weight: u64,
+}
+
+pub struct FairShare {
+    // 所有已知线程的vruntime，线程结束时通过 `remove` 删除
+    entities: BTreeMap<ThreadId, Entity>,
+    ready: Vec<ThreadId>,
+    // 单调递增的最小vruntime
+    min_vruntime: u64,
+}
+
+impl Default for FairShare {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FairShare {
+    pub fn new() -> Self {
+        FairShare {
+            entities: BTreeMap::new(),
+            ready: Vec::new(),
+            min_vruntime: 0,
+        }
+    }
+
+    fn vruntime(&self, id: ThreadId) -> u64 {
+        self.entities.get(&id).map_or(self.min_vruntime, |entity| entity.vruntime)
+    }
+
+    // vruntime最小的就绪线程在 `ready` 中的下标，相同时取最早进入的
+    fn leftmost(&self) -> Option<usize> {
+        let mut best: Option<(usize, u64)> = None;
+        for (index, &id) in self.ready.iter().enumerate() {
+            let vruntime = self.vruntime(id);
+            match best {
+                Some((_, best_vruntime)) if best_vruntime <= vruntime => {}
+                _ => best = Some((index, vruntime)),
+            }
+        }
+        best.map(|(index, _)| index)
+    }
+}
+
+impl Scheduler for FairShare {
+    fn name(&self) -> &'static str {
+        "fair-share"
+    }
+
+    // 线程的vruntime在这里就登记好，`enqueue` 和 `tick` 只修改已有的记录，不会向 `BTreeMap` 插入新节点
+    fn reserve(&mut self, id: ThreadId, priority: Priority, threads: usize) {
+        let min_vruntime = self.min_vruntime;
+        self.entities.entry(id).or_insert(Entity {
+            vruntime: min_vruntime,
+            weight: weight(priority),
+        });
+        self.ready.reserve(threads.saturating_sub(self.ready.len()));
+    }
+
+    fn enqueue(&mut self, id: ThreadId, priority: Priority) {
+        let min_vruntime = self.min_vruntime;
+        let entity = self.entities.entry(id).or_insert(Entity {
+            vruntime: min_vruntime,
+            weight: weight(priority),
+        });
+        entity.vruntime = entity.vruntime.max(min_vruntime);
+        entity.weight = weight(priority);
+        self.ready.push(id);
+    }
+
+    fn pick_next(&mut self) -> Option<ThreadId> {
+        let index = self.leftmost()?;
+        let id = self.ready.remove(index);
+        self.min_vruntime = self.min_vruntime.max(self.vruntime(id));
+        Some(id)
+    }
+
+    fn tick(&mut self, current: ThreadId) -> bool {
+        let vruntime = match self.entities.get_mut(&current) {
+            Some(entity) => {
+                entity.vruntime += BASE_SLICE * BASE_SLICE / entity.weight;
+                entity.vruntime
+            }
+            // 没有经过 `reserve` 的线程按刚从最小值开始运行了一个tick计算。这里在中断中运行，不为它插入记录
+            None => self.min_vruntime + BASE_SLICE,
+        };
+        match self.leftmost() {
+            Some(index) => vruntime > self.vruntime(self.ready[index]) + GRANULARITY,
+            None => false,
+        }
+    }
+
+    fn remove(&mut self, id: ThreadId) {
+        self.entities.remove(&id);
+        self.ready.retain(|&ready| ready != id);
+    }
+}
+
+#[test_case]
+fn test_fair_weight() {
+    assert_eq!(weight(DEFAULT_PRIORITY), BASE_SLICE);
+    assert!(weight(DEFAULT_PRIORITY + 1) > BASE_SLICE);
+    assert!(weight(DEFAULT_PRIORITY - 1) < BASE_SLICE);
+}
+
+#[test_case]
+fn test_fair_share_favors_higher_priority() {
+    let mut scheduler = FairShare::new();
+    let (high, low) = (ThreadId(100), ThreadId(101));
+    scheduler.enqueue(high, DEFAULT_PRIORITY + 4);
+    scheduler.enqueue(low, DEFAULT_PRIORITY);
+
+    // 模拟100个tick，统计每个线程实际运行的tick数
+    let (mut high_ticks, mut low_ticks) = (0, 0);
+    let mut current = scheduler.pick_next().unwrap();
+    for _ in 0..100 {
+        if current == high {
+            high_ticks += 1;
+        } else {
+            low_ticks += 1;
+        }
+        if scheduler.tick(current) {
+            let priority = if current == high { DEFAULT_PRIORITY + 4 } else { DEFAULT_PRIORITY };
+            scheduler.enqueue(current, priority);
+            current = scheduler.pick_next().unwrap();
+        }
+    }
+    // 两者都能运行，高优先级线程获得更多CPU时间
+    assert!(low_ticks > 0);
+    assert!(high_ticks > low_ticks);
+}
+
+#[test_case]
+fn test_fair_share_new_thread_starts_at_min_vruntime() {
+    let mut scheduler = FairShare::new();
+    let (old, new) = (ThreadId(100), ThreadId(101));
+    scheduler.enqueue(old, DEFAULT_PRIORITY);
+    assert_eq!(scheduler.pick_next(), Some(old));
+    for _ in 0..50 {
+        scheduler.tick(old);
+    }
+    scheduler.enqueue(old, DEFAULT_PRIORITY);
+    scheduler.pick_next();
+    // 新线程从最小vruntime开始，而不是0，所以不会独占CPU很久
+    scheduler.enqueue(new, DEFAULT_PRIORITY);
+    assert!(scheduler.vruntime(new) >= scheduler.min_vruntime);
+    assert!(scheduler.vruntime(new) > 0);
+}
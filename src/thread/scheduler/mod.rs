@@ -0,0 +1,117 @@
+// 调度策略
+// 线程管理(栈、上下文切换、阻塞和唤醒)与“下一个运行谁”的决策分开：后者由实现了 `Scheduler` trait 的调度策略负责，
+// 启动时通过 `SchedulerPolicy` 选择其中一种：
+// - `RoundRobin`: 轮转调度，所有线程轮流运行固定的时间片
+// - `Priority`: 静态优先级调度，总是运行优先级最高的线程，等待过久的线程优先级逐渐提高(老化)，防止低优先级线程饿死
+// - `FairShare`: 类似Linux CFS的公平调度，总是运行虚拟运行时间最少的线程，优先级越高虚拟时间增长越慢
+//
+// 内核启动时使用的策略 `BOOT_POLICY` 和全局分配器一样通过cargo特性选择，最多只能开启一个：
+// - `sched-round-robin`: 轮转调度，什么都不开启时也使用它
+// - `sched-priority`: 优先级调度
+// - `sched-fair-share`: 公平调度
+// 例如 `cargo run --features sched-fair-share`
+//
+// 所有时间都以定时器中断的次数(tick)为单位
+
+use alloc::boxed::Box;
+
+use super::ThreadId;
+
+pub mod fair;
+pub mod priority;
+pub mod round_robin;
+
+// 线程优先级，数值越大越优先
+pub type Priority = u8;
+
+pub const MIN_PRIORITY: Priority = 0;
+pub const DEFAULT_PRIORITY: Priority = 8;
+pub const MAX_PRIORITY: Priority = 15;
+
+// 默认的时间片长度(tick)
+pub const DEFAULT_QUANTUM: u64 = 1;
+// 默认的老化间隔：就绪线程每等待这么多tick，有效优先级提高1
+pub const DEFAULT_AGING_INTERVAL: u64 = 4;
+
+// 调度策略的接口
+// 空闲线程由线程管理自己处理，不会交给调度策略；已经阻塞或结束的线程也不在就绪集合中
+// 这些方法都在关闭中断的情况下调用，不能再等待中断
+pub trait Scheduler: Send {
+    // 策略的名称，用于输出统计信息
+    fn name(&self) -> &'static str;
+
+    // 新线程创建时在线程上下文中调用，`threads` 是当前的线程总数，同时就绪的线程不会比它更多
+    // 在这里预先分配 `enqueue` 需要的空间：抢占发生在定时器中断中，那时不能进行堆分配
+    fn reserve(&mut self, id: ThreadId, priority: Priority, threads: usize);
+
+    // 线程进入就绪状态：新创建、被抢占、主动让出或被唤醒
+    fn enqueue(&mut self, id: ThreadId, priority: Priority);
+
+    // 从就绪集合中取出下一个要运行的线程，集合为空时返回 `None`
+    fn pick_next(&mut self) -> Option<ThreadId>;
+
+    // 当前线程又运行了一个tick，返回 `true` 表示应该抢占它
+    fn tick(&mut self, current: ThreadId) -> bool;
+
+    // 线程结束，丢弃策略为它保存的状态
+    fn remove(&mut self, _id: ThreadId) {}
+}
+
+// 启动时选择的调度策略及其参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedulerPolicy {
+    // 每个线程运行 `quantum` 个tick后让给就绪队列中的下一个线程
+    RoundRobin { quantum: u64 },
+    // 同一优先级内按 `quantum` 轮转；就绪线程每等待 `aging_interval` 个tick有效优先级提高1
+    Priority { quantum: u64, aging_interval: u64 },
+    // 按虚拟运行时间公平分配CPU
+    FairShare,
+}
+
+#[cfg(any(
+    all(feature = "sched-round-robin", feature = "sched-priority"),
+    all(feature = "sched-round-robin", feature = "sched-fair-share"),
+    all(feature = "sched-priority", feature = "sched-fair-share"),
+))]
+compile_error!("only one of the `sched-round-robin`, `sched-priority` and `sched-fair-share` features can be enabled");
+
+// 根据开启的特性选择内核启动时使用的调度策略
+#[cfg(feature = "sched-priority")]
+pub const BOOT_POLICY: SchedulerPolicy = SchedulerPolicy::Priority {
+    quantum: DEFAULT_QUANTUM,
+    aging_interval: DEFAULT_AGING_INTERVAL,
+};
+#[cfg(feature = "sched-fair-share")]
+pub const BOOT_POLICY: SchedulerPolicy = SchedulerPolicy::FairShare;
+#[cfg(not(any(feature = "sched-priority", feature = "sched-fair-share")))]
+pub const BOOT_POLICY: SchedulerPolicy = SchedulerPolicy::RoundRobin { quantum: DEFAULT_QUANTUM };
+
+impl Default for SchedulerPolicy {
+    fn default() -> Self {
+        SchedulerPolicy::RoundRobin { quantum: DEFAULT_QUANTUM }
+    }
+}
+
+impl SchedulerPolicy {
+    // 创建对应的调度策略
+    pub(super) fn build(self) -> Box<dyn Scheduler> {
+        match self {
+            SchedulerPolicy::RoundRobin { quantum } => Box::new(round_robin::RoundRobin::new(quantum)),
+            SchedulerPolicy::Priority { quantum, aging_interval } => {
+                Box::new(priority::PriorityScheduler::new(quantum, aging_interval))
+            }
+            SchedulerPolicy::FairShare => Box::new(fair::FairShare::new()),
+        }
+    }
+}
+
+#[test_case]
+fn test_policy_names() {
+    assert_eq!(SchedulerPolicy::default().build().name(), "round-robin");
+    let priority = SchedulerPolicy::Priority {
+        quantum: DEFAULT_QUANTUM,
+        aging_interval: DEFAULT_AGING_INTERVAL,
+    };
+    assert_eq!(priority.build().name(), "priority");
+    assert_eq!(SchedulerPolicy::FairShare.build().name(), "fair-share");
+}
@@ -0,0 +1,142 @@
+// 静态优先级调度(带老化)
+// 总是运行有效优先级最高的就绪线程，同一优先级内按进入就绪状态的先后轮转。
+// 只按静态优先级调度时，只要一直有高优先级线程就绪，低优先级线程就永远得不到运行。所以就绪线程每等待 `aging_interval`
+// 个tick，有效优先级提高1(不超过 `MAX_PRIORITY`)；线程被选中运行后恢复为静态优先级
+
+use alloc::vec::Vec;
+
+use super::{Priority, Scheduler, DEFAULT_PRIORITY, MAX_PRIORITY};
+use crate::thread::ThreadId;
+
+struct ReadyThread {
+    id: ThreadId,
+    // 线程的静态优先级
+    base: Priority,
+    // 加上老化提升之后的优先级
+    effective: Priority,
+    // 自上次提升以来等待的tick数
+    waited: u64,
+}
+
+pub struct PriorityScheduler {
+    // 按进入就绪状态的先后排列，选择时取有效优先级最高的第一个，从而在同一优先级内轮转
+    ready: Vec<ReadyThread>,
+    quantum: u64,
+    aging_interval: u64,
+    // 当前线程的静态优先级和在这个时间片内已经运行的tick数
+    current_priority: Priority,
+    used: u64,
+}
+
+impl PriorityScheduler {
+    pub fn new(quantum: u64, aging_interval: u64) -> Self {
+        PriorityScheduler {
+            ready: Vec::new(),
+            quantum: quantum.max(1),
+            aging_interval: aging_interval.max(1),
+            // 引导线程没有经过 `enqueue`，按默认优先级对待
+            current_priority: DEFAULT_PRIORITY,
+            used: 0,
+        }
+    }
+
+    // 有效优先级最高的就绪线程在 `ready` 中的下标，优先级相同时取最早进入的
+    fn highest(&self) -> Option<usize> {
+        let mut best: Option<usize> = None;
+        for (index, thread) in self.ready.iter().enumerate() {
+            match best {
+                Some(b) if self.ready[b].effective >= thread.effective => {}
+                _ => best = Some(index),
+            }
+        }
+        best
+    }
+
+    // 所有就绪线程又等待了一个tick
+    fn age(&mut self) {
+        for thread in self.ready.iter_mut() {
+            thread.waited += 1;
+            if thread.waited >= self.aging_interval {
+                thread.waited = 0;
+                thread.effective = (thread.effective + 1).min(MAX_PRIORITY);
+            }
+        }
+    }
+}
+
+impl Scheduler for PriorityScheduler {
+    fn name(&self) -> &'static str {
+        "priority"
+    }
+
+    fn reserve(&mut self, _id: ThreadId, _priority: Priority, threads: usize) {
+        self.ready.reserve(threads.saturating_sub(self.ready.len()));
+    }
+
+    fn enqueue(&mut self, id: ThreadId, priority: Priority) {
+        let base = priority.min(MAX_PRIORITY);
+        self.ready.push(ReadyThread {
+            id,
+            base,
+            effective: base,
+            waited: 0,
+        });
+    }
+
+    fn pick_next(&mut self) -> Option<ThreadId> {
+        let index = self.highest()?;
+        let thread = self.ready.remove(index);
+        self.current_priority = thread.base;
+        self.used = 0;
+        Some(thread.id)
+    }
+
+    fn tick(&mut self, _current: ThreadId) -> bool {
+        self.used += 1;
+        self.age();
+        match self.highest() {
+            // 有更高优先级的线程就绪时立即抢占；时间片用完时让给同等或更高优先级的线程
+            Some(index) => {
+                let best = self.ready[index].effective;
+                best > self.current_priority || (self.used >= self.quantum && best >= self.current_priority)
+            }
+            None => false,
+        }
+    }
+}
+
+#[test_case]
+fn test_priority_picks_highest_first() {
+    let mut scheduler = PriorityScheduler::new(1, 100);
+    let (low, high1, high2) = (ThreadId(100), ThreadId(101), ThreadId(102));
+    scheduler.enqueue(low, 2);
+    scheduler.enqueue(high1, 10);
+    scheduler.enqueue(high2, 10);
+
+    assert_eq!(scheduler.pick_next(), Some(high1));
+    // 时间片用完，同一优先级的另一个线程就绪，应该被抢占
+    assert!(scheduler.tick(high1));
+    scheduler.enqueue(high1, 10);
+    assert_eq!(scheduler.pick_next(), Some(high2));
+    assert_eq!(scheduler.pick_next(), Some(high1));
+    // 只剩低优先级线程时不抢占高优先级线程
+    assert!(!scheduler.tick(high1));
+    assert_eq!(scheduler.pick_next(), Some(low));
+}
+
+#[test_case]
+fn test_priority_aging_prevents_starvation() {
+    let mut scheduler = PriorityScheduler::new(1, 2);
+    let (low, high) = (ThreadId(100), ThreadId(101));
+    scheduler.enqueue(low, 4);
+    scheduler.enqueue(high, 6);
+    assert_eq!(scheduler.pick_next(), Some(high));
+
+    // 低优先级线程每等待2个tick提高1，等待4个tick后与高优先级线程相同，时间片用完时轮到它
+    for _ in 0..3 {
+        assert!(!scheduler.tick(high));
+    }
+    assert!(scheduler.tick(high));
+    scheduler.enqueue(high, 6);
+    assert_eq!(scheduler.pick_next(), Some(low));
+}
@@ -0,0 +1,81 @@
+// 轮转调度
+// 就绪线程排成一个先进先出的队列，当前线程用完时间片后回到队尾。不考虑优先级
+
+use alloc::collections::VecDeque;
+
+use super::{Priority, Scheduler};
+use crate::thread::ThreadId;
+
+pub struct RoundRobin {
+    ready: VecDeque<ThreadId>,
+    // 时间片长度(tick)，至少为1
+    quantum: u64,
+    // 当前线程在这个时间片内已经运行的tick数
+    used: u64,
+}
+
+impl RoundRobin {
+    pub fn new(quantum: u64) -> Self {
+        RoundRobin {
+            ready: VecDeque::new(),
+            quantum: quantum.max(1),
+            used: 0,
+        }
+    }
+}
+
+impl Scheduler for RoundRobin {
+    fn name(&self) -> &'static str {
+        "round-robin"
+    }
+
+    fn reserve(&mut self, _id: ThreadId, _priority: Priority, threads: usize) {
+        self.ready.reserve(threads.saturating_sub(self.ready.len()));
+    }
+
+    fn enqueue(&mut self, id: ThreadId, _priority: Priority) {
+        self.ready.push_back(id);
+    }
+
+    fn pick_next(&mut self) -> Option<ThreadId> {
+        self.used = 0;
+        self.ready.pop_front()
+    }
+
+    fn tick(&mut self, _current: ThreadId) -> bool {
+        self.used += 1;
+        self.used >= self.quantum
+    }
+}
+
+#[test_case]
+fn test_round_robin_order_and_quantum() {
+    let mut rr = RoundRobin::new(2);
+    let (a, b) = (ThreadId(100), ThreadId(101));
+    rr.enqueue(a, 0);
+    rr.enqueue(b, 15);
+
+    // 忽略优先级，按进入队列的顺序运行
+    assert_eq!(rr.pick_next(), Some(a));
+    assert!(!rr.tick(a));
+    assert!(rr.tick(a));
+    rr.enqueue(a, 0);
+    assert_eq!(rr.pick_next(), Some(b));
+    // 切换线程后重新计算时间片
+    assert!(!rr.tick(b));
+    assert_eq!(rr.pick_next(), Some(a));
+    assert_eq!(rr.pick_next(), None);
+}
+
+#[test_case]
+fn test_round_robin_reserve() {
+    let mut rr = RoundRobin::new(1);
+    rr.reserve(ThreadId(100), 0, 4);
+    let capacity = rr.ready.capacity();
+    assert!(capacity >= 4);
+    // 预留之后入队不再扩容
+    for id in 100..104 {
+        rr.enqueue(ThreadId(id), 0);
+    }
+    assert_eq!(rr.ready.capacity(), capacity);
+}
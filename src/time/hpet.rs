@@ -0,0 +1,151 @@
+// 高精度事件定时器(HPET)
+// HPET有一个以固定频率(通常十几MHz)递增的主计数器，计数器周期以飞秒为单位写在能力寄存器中，不需要校准。
+// 寄存器通过MMIO访问，地址来自ACPI的HPET表。这里只使用主计数器作为时间基准，不使用它的比较器产生中断
+
+use core::time::Duration;
+use spin::Once;
+use x86_64::structures::paging::mapper::MapToError;
+use x86_64::structures::paging::Size4KiB;
+use x86_64::{PhysAddr, VirtAddr};
+
+use crate::acpi::{self, AcpiError, GenericAddress};
+use crate::memory::paging;
+
+// 寄存器区域的大小
+const HPET_MMIO_SIZE: u64 = 1024;
+// 通用能力和ID寄存器：高32位是主计数器的周期(飞秒)
+const CAPABILITIES: usize = 0x000;
+// 通用配置寄存器：第0位开启主计数器，第1位开启传统替换路由
+const CONFIGURATION: usize = 0x010;
+const ENABLE_CNF: u64 = 1;
+// 主计数器
+const MAIN_COUNTER: usize = 0x0f0;
+// 规范规定计数器周期不超过100纳秒
+const MAX_PERIOD_FS: u64 = 100_000_000;
+const FEMTOS_PER_NANO: u64 = 1_000_000;
+
+// 无法使用HPET的原因
+#[derive(Debug)]
+pub enum HpetError {
+    // 无法读取ACPI表
+    Acpi(AcpiError),
+    // ACPI表中没有HPET
+    NotPresent,
+    // 寄存器不在系统内存中
+    UnsupportedAddressSpace,
+    // 能力寄存器中的计数器周期无效
+    InvalidPeriod,
+    // 映射寄存器失败
+    Map(MapToError<Size4KiB>),
+}
+
+impl From<MapToError<Size4KiB>> for HpetError {
+    fn from(err: MapToError<Size4KiB>) -> Self {
+        HpetError::Map(err)
+    }
+}
+
+pub struct Hpet {
+    base: VirtAddr,
+    // 主计数器每加1经过的飞秒数
+    period_fs: u64,
+    // 32位计数器只有低32位有效，计算差值时需要截断
+    counter_mask: u64,
+}
+
+impl Hpet {
+    // 创建驱动并从能力寄存器读取计数周期
+    /// # Safety
+    ///
+    /// 调用者必须保证 `base` 处映射着HPET的寄存器
+    unsafe fn new(base: VirtAddr, counter_64bit: bool) -> Self {
+        let mut hpet = Hpet {
+            base,
+            period_fs: 0,
+            counter_mask: if counter_64bit { u64::MAX } else { u64::from(u32::MAX) },
+        };
+        hpet.period_fs = hpet.read(CAPABILITIES) >> 32;
+        hpet
+    }
+
+    fn read(&self, offset: usize) -> u64 {
+        unsafe { (self.base + offset as u64).as_ptr::<u64>().read_volatile() }
+    }
+
+    fn write(&self, offset: usize, value: u64) {
+        unsafe { (self.base + offset as u64).as_mut_ptr::<u64>().write_volatile(value) }
+    }
+
+    // 开启主计数器。不使用传统替换路由，PIT和RTC的中断保持不变
+    fn enable(&self) {
+        self.write(CONFIGURATION, self.read(CONFIGURATION) | ENABLE_CNF);
+    }
+
+    // 主计数器当前的值
+    pub fn counter(&self) -> u64 {
+        self.read(MAIN_COUNTER) & self.counter_mask
+    }
+
+    // 主计数器的周期(飞秒)
+    pub fn period_fs(&self) -> u64 {
+        self.period_fs
+    }
+
+    // 主计数器的频率(Hz)
+    pub fn frequency(&self) -> u64 {
+        1_000_000_000_000_000 / self.period_fs
+    }
+
+    // 计数器从 `start` 到 `end` 经过的时间，32位计数器回绕一次也能正确计算
+    pub fn elapsed(&self, start: u64, end: u64) -> Duration {
+        let counts = end.wrapping_sub(start) & self.counter_mask;
+        Duration::from_nanos((u128::from(counts) * u128::from(self.period_fs) / u128::from(FEMTOS_PER_NANO)) as u64)
+    }
+
+    // 忙等待 `duration`，不依赖中断
+    pub fn spin_wait(&self, duration: Duration) {
+        let start = self.counter();
+        while self.elapsed(start, self.counter()) < duration {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+// 第一次初始化的结果。失败也记录下来，否则每次重试都会重新映射寄存器，白白占用一段MMIO虚拟地址
+static HPET: Once<Result<Hpet, HpetError>> = Once::new();
+
+// 从ACPI表中找到HPET，映射寄存器并开启主计数器
+// 需要在 `memory::init` 和 `allocator::init_heap` 之后调用。重复调用时返回第一次初始化的结果，包括失败的原因
+pub fn init() -> Result<&'static Hpet, &'static HpetError> {
+    HPET.call_once(|| {
+        let acpi = acpi::init().map_err(HpetError::Acpi)?;
+        let table = acpi.hpet.as_ref().ok_or(HpetError::NotPresent)?;
+        if table.base_address.address_space != GenericAddress::SYSTEM_MEMORY {
+            return Err(HpetError::UnsupportedAddressSpace);
+        }
+        let base = unsafe { paging::map_mmio(PhysAddr::new(table.base_address.address), HPET_MMIO_SIZE)? };
+        let hpet = unsafe { Hpet::new(base, table.counter_64bit) };
+        if hpet.period_fs == 0 || hpet.period_fs > MAX_PERIOD_FS {
+            return Err(HpetError::InvalidPeriod);
+        }
+        hpet.enable();
+        Ok(hpet)
+    })
+    .as_ref()
+}
+
+// 已经初始化的HPET
+pub fn get() -> Option<&'static Hpet> {
+    HPET.get().and_then(|hpet| hpet.as_ref().ok())
+}
+
+#[test_case]
+fn test_hpet_counter_advances() {
+    // QEMU的默认机器带有HPET
+    let hpet = init().expect("HPET not available");
+    assert!(hpet.frequency() >= 10_000_000);
+    let start = hpet.counter();
+    hpet.spin_wait(Duration::from_millis(1));
+    let elapsed = hpet.elapsed(start, hpet.counter());
+    assert!(elapsed >= Duration::from_millis(1) && elapsed < Duration::from_millis(50));
+}
@@ -0,0 +1,126 @@
+// 单调时钟上的时间点，类似标准库的 `std::time::Instant`
+// TSC校准之后由TSC换算得到，精度为纳秒级；校准之前退化为按tick计算的 `uptime`。
+// 校准时TSC的时间从当时的 `uptime` 接着往下走，所以校准前后取得的 `Instant` 也可以互相比较
+
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+use core::time::Duration;
+
+use super::tsc;
+
+// 内部保存开机以来的纳秒数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(u64);
+
+impl Instant {
+    // 当前时间点
+    pub fn now() -> Self {
+        Instant(tsc::nanos().unwrap_or_else(|| super::uptime().as_nanos() as u64))
+    }
+
+    // 从这个时间点到现在经过的时间
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().saturating_duration_since(*self)
+    }
+
+    // 从 `earlier` 到这个时间点经过的时间，`earlier` 更晚时返回0
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.saturating_duration_since(earlier)
+    }
+
+    // 从 `earlier` 到这个时间点经过的时间，`earlier` 更晚时返回 `None`
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.0.checked_sub(earlier.0).map(Duration::from_nanos)
+    }
+
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or_default()
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        let nanos = u64::try_from(duration.as_nanos()).ok()?;
+        self.0.checked_add(nanos).map(Instant)
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        let nanos = u64::try_from(duration.as_nanos()).ok()?;
+        self.0.checked_sub(nanos).map(Instant)
+    }
+
+    // 开机以来的纳秒数，便于输出测量结果
+    pub fn as_nanos(&self) -> u64 {
+        self.0
+    }
+}
+
+// 和标准库一样，运算溢出时panic
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        self.checked_add(duration).expect("overflow when adding duration to instant")
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, duration: Duration) {
+        *self = *self + duration;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, duration: Duration) -> Instant {
+        self.checked_sub(duration).expect("overflow when subtracting duration from instant")
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, duration: Duration) {
+        *self = *self - duration;
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+#[test_case]
+fn test_instant_arithmetic() {
+    let start = Instant(1_000);
+    let later = start + Duration::from_nanos(500);
+    assert_eq!(later.as_nanos(), 1_500);
+    assert_eq!(later - start, Duration::from_nanos(500));
+    assert_eq!(later - Duration::from_nanos(500), start);
+    // 顺序颠倒时得到0而不是panic
+    assert_eq!(start - later, Duration::ZERO);
+    assert_eq!(start.checked_duration_since(later), None);
+    assert_eq!(start.checked_sub(Duration::from_micros(2)), None);
+
+    let mut instant = start;
+    instant += Duration::from_micros(1);
+    instant -= Duration::from_nanos(1);
+    assert_eq!(instant.as_nanos(), 1_999);
+}
+
+#[test_case]
+fn test_instant_high_resolution() {
+    // 测试内核在启动时已经校准了TSC
+    assert!(tsc::frequency().is_some());
+    let start = Instant::now();
+    let mut end = Instant::now();
+    while end == start {
+        end = Instant::now();
+    }
+    // 两次读取之间的间隔远小于一个tick
+    assert!(end - start < Duration::from_nanos(super::tick_period_ns()));
+
+    let start = Instant::now();
+    super::sleep_ms(20);
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(19) && elapsed < Duration::from_millis(100));
+}
@@ -0,0 +1,201 @@
+// 时间基准
+// 定时器中断每触发一次，中断分发函数就调用 `tick` 把全局的tick计数加1，并累加经过的纳秒数。
+// 在此基础上提供开机以来的时间 `uptime`、阻塞等待 `sleep_ms` 和异步的 `Delay`，驱动和调度器都以此为时间基准。
+// 需要比tick更高的精度(例如测量中断延迟)时使用 `Instant`，它由HPET或PIT校准过的TSC提供纳秒级的单调时钟。
+// 日期和时间(墙上时间)来自CMOS实时时钟，见 `wall_clock`
+
+use alloc::collections::BTreeMap;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+pub mod hpet;
+pub mod instant;
+pub mod pit;
+pub mod rtc;
+pub mod tsc;
+
+pub use instant::Instant;
+pub use rtc::{wall_clock, DateTime};
+
+// 定时器中断的频率(Hz)，每个tick 10毫秒
+pub const TIMER_FREQUENCY_HZ: u32 = 100;
+
+// 开机以来的定时器中断次数
+static TICKS: AtomicU64 = AtomicU64::new(0);
+// 开机以来经过的纳秒数。每个tick按当时的周期累加，即使中途修改了频率也能保持正确
+static UPTIME_NS: AtomicU64 = AtomicU64::new(0);
+// 当前定时器中断的周期(纳秒)，初始值为BIOS设置的PIT默认周期
+static TICK_PERIOD_NS: AtomicU64 = AtomicU64::new(54_925_439);
+
+// 设置PIT的频率，并把 `tick` 注册为定时器中断(IRQ0)的处理函数。需要在开启中断之前调用
+pub fn init() {
+    set_frequency(TIMER_FREQUENCY_HZ);
+    crate::interrupts::register_irq(crate::interrupts::InterruptIndex::Timer.irq(), tick)
+        .expect("timer IRQ already registered");
+}
+
+// 开启HPET(如果平台有)并用它校准TSC，之后 `Instant::now` 的精度达到纳秒级。返回校准TSC时使用的参考时钟
+// 需要在 `memory::init` 和 `allocator::init_heap` 之后调用
+pub fn init_high_resolution() -> tsc::Reference {
+    // 没有HPET时 `tsc::calibrate` 会改用PIT，所以这里不需要处理错误
+    let _ = hpet::init();
+    tsc::calibrate()
+}
+
+// 把PIT设置为尽量接近 `hz` 的频率，并以此作为tick的周期，返回实际的频率
+pub fn set_frequency(hz: u32) -> u32 {
+    let actual = pit::set_frequency(hz);
+    set_tick_period(pit::period_ns());
+    actual
+}
+
+// 更换定时器中断来源(例如改用本地APIC定时器)后，由设置它的代码告知新的周期
+pub(crate) fn set_tick_period(period_ns: u64) {
+    TICK_PERIOD_NS.store(period_ns, Ordering::Relaxed);
+}
+
+// 每个tick的时间(纳秒)
+pub fn tick_period_ns() -> u64 {
+    TICK_PERIOD_NS.load(Ordering::Relaxed)
+}
+
+// 定时器中断的处理函数：计数加1，并唤醒到期的 `Delay`
+pub(crate) fn tick() {
+    let ticks = TICKS.fetch_add(1, Ordering::Relaxed) + 1;
+    UPTIME_NS.fetch_add(tick_period_ns(), Ordering::Relaxed);
+    wake_expired(ticks);
+}
+
+// 开机以来的定时器中断次数
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+// 开机以来经过的时间，精度为一个tick
+pub fn uptime() -> Duration {
+    Duration::from_nanos(UPTIME_NS.load(Ordering::Relaxed))
+}
+
+// 把时间长度换算成tick数，向上取整，保证等待的时间不少于 `duration`
+pub fn duration_to_ticks(duration: Duration) -> u64 {
+    let period = u128::from(tick_period_ns());
+    duration.as_nanos().div_ceil(period) as u64
+}
+
+// 阻塞当前执行流至少 `ms` 毫秒
+// 等待期间执行 `hlt`，其他线程仍然可以被调度。必须在开启中断的情况下调用，否则tick不会增加
+pub fn sleep_ms(ms: u64) {
+    assert!(interrupts::are_enabled(), "sleep_ms called with interrupts disabled");
+    let deadline = ticks() + duration_to_ticks(Duration::from_millis(ms));
+    while ticks() < deadline {
+        x86_64::instructions::hlt();
+    }
+}
+
+// 等待中的 `Delay` 的waker，以 `Delay` 的编号为键，值为到期的tick数和waker
+// 定时器中断中使用 `try_lock`，加锁规则见 `interrupts::irq`
+static WAKERS: Mutex<BTreeMap<u64, (u64, Waker)>> = Mutex::new(BTreeMap::new());
+
+// 唤醒所有在 `now` 之前到期的 `Delay`
+fn wake_expired(now: u64) {
+    if let Some(mut wakers) = WAKERS.try_lock() {
+        wakers.retain(|_, (deadline, waker)| {
+            if *deadline <= now {
+                waker.wake_by_ref();
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+// 在指定时间之后完成的future，用于异步任务中的等待
+// 到期前被轮询时登记waker，由定时器中断在到期时唤醒，不会占用CPU
+pub struct Delay {
+    id: u64,
+    deadline: u64,
+}
+
+impl Delay {
+    // 从现在起至少等待 `duration`
+    pub fn new(duration: Duration) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Delay {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            deadline: ticks() + duration_to_ticks(duration),
+        }
+    }
+
+    // 从现在起至少等待 `ms` 毫秒
+    pub fn from_ms(ms: u64) -> Self {
+        Delay::new(Duration::from_millis(ms))
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if ticks() >= self.deadline {
+            return Poll::Ready(());
+        }
+        interrupts::without_interrupts(|| {
+            // 同一个 `Delay` 多次轮询时替换之前登记的waker
+            WAKERS
+                .lock()
+                .insert(self.id, (self.deadline, cx.waker().clone()));
+        });
+        // 登记之前可能已经到期，再检查一次，避免错过唤醒
+        if ticks() >= self.deadline {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Delay {
+    fn drop(&mut self) {
+        interrupts::without_interrupts(|| {
+            WAKERS.lock().remove(&self.id);
+        });
+    }
+}
+
+#[test_case]
+fn test_ticks_advance() {
+    let start = ticks();
+    while ticks() == start {
+        x86_64::instructions::hlt();
+    }
+    assert!(uptime() > Duration::from_nanos(0));
+}
+
+#[test_case]
+fn test_sleep_ms() {
+    let start = uptime();
+    sleep_ms(30);
+    assert!(uptime() - start >= Duration::from_millis(30));
+}
+
+#[test_case]
+fn test_delay_future() {
+    use crate::task::simple_executor::SimpleExecutor;
+    use crate::task::Task;
+
+    let start = uptime();
+    let mut executor = SimpleExecutor::new();
+    executor.spawn(Task::new(async {
+        Delay::from_ms(20).await;
+    }));
+    executor.run();
+    assert!(uptime() - start >= Duration::from_millis(20));
+    // 完成的 `Delay` 不会留下waker
+    assert!(interrupts::without_interrupts(|| WAKERS.lock().is_empty()));
+}
@@ -0,0 +1,91 @@
+// 可编程间隔定时器(PIT, Intel 8253/8254)
+// PIT的输入时钟为 1.193182 MHz，通道0每数完一个“分频值”就在IRQ0上产生一次中断。
+// BIOS默认分频值为65536，约18.2 Hz，精度太低，这里把它设置成需要的频率
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+
+// PIT的输入时钟频率(Hz)
+pub const BASE_FREQUENCY: u32 = 1_193_182;
+
+// 通道0的数据端口
+const CHANNEL0_PORT: u16 = 0x40;
+// 通道2的数据端口。通道2原本驱动PC喇叭，不产生中断，可以在关闭中断时用来轮询计时
+const CHANNEL2_PORT: u16 = 0x42;
+// 系统控制端口B(原8255 PPI的端口B，不是8042键盘控制器)：第0位是通道2的门控(GATE2)，第1位连接喇叭，第5位反映通道2的输出(OUT2)
+const PORT_B: u16 = 0x61;
+// 模式/命令寄存器
+const COMMAND_PORT: u16 = 0x43;
+// 选择通道0 | 先写低字节再写高字节 | 模式3(方波发生器) | 二进制计数
+const COMMAND_CHANNEL0_SQUARE_WAVE: u8 = 0b0011_0110;
+// 选择通道2 | 先写低字节再写高字节 | 模式0(计数结束时输出变高) | 二进制计数
+const COMMAND_CHANNEL2_ONE_SHOT: u8 = 0b1011_0000;
+
+// 两个端口需要按顺序连续写入，用锁防止交错
+static PORTS: Mutex<()> = Mutex::new(());
+// 通道0实际的分频值。0表示65536，即BIOS默认值
+static DIVISOR: AtomicU32 = AtomicU32::new(65536);
+
+// 把通道0设置为尽量接近 `hz` 的频率，返回实际的频率
+// 分频值只能是1..=65536的整数，所以实际频率在约18.2 Hz到1.19 MHz之间，并且不一定正好等于 `hz`
+pub fn set_frequency(hz: u32) -> u32 {
+    let divisor = (BASE_FREQUENCY / hz.max(1)).clamp(1, 65536);
+    let _guard = PORTS.lock();
+    let mut command = Port::<u8>::new(COMMAND_PORT);
+    let mut data = Port::<u8>::new(CHANNEL0_PORT);
+    // 写入的分频值65536在16位寄存器中表示为0
+    let value = (divisor & 0xffff) as u16;
+    unsafe {
+        command.write(COMMAND_CHANNEL0_SQUARE_WAVE);
+        data.write(value as u8);
+        data.write((value >> 8) as u8);
+    }
+    DIVISOR.store(divisor, Ordering::Relaxed);
+    frequency()
+}
+
+// 通道0当前的实际频率(Hz)
+pub fn frequency() -> u32 {
+    BASE_FREQUENCY / DIVISOR.load(Ordering::Relaxed)
+}
+
+// 每次中断之间的时间(纳秒)
+pub fn period_ns() -> u64 {
+    u64::from(DIVISOR.load(Ordering::Relaxed)) * 1_000_000_000 / u64::from(BASE_FREQUENCY)
+}
+
+// 用通道2忙等待 `ms` 毫秒(最多约54毫秒)，不依赖中断，用于校准其他时钟
+// 通道2的计数值是16位的，所以超过上限的等待时间会被截断为上限
+pub fn spin_wait_ms(ms: u32) {
+    let count = (u64::from(BASE_FREQUENCY) * u64::from(ms) / 1000).clamp(1, 0xffff) as u16;
+    let _guard = PORTS.lock();
+    let mut command = Port::<u8>::new(COMMAND_PORT);
+    let mut data = Port::<u8>::new(CHANNEL2_PORT);
+    let mut port_b = Port::<u8>::new(PORT_B);
+    unsafe {
+        // 关闭喇叭，先拉低门控，写入计数值后再拉高门控开始计数
+        let value = port_b.read() & !0b11;
+        port_b.write(value);
+        command.write(COMMAND_CHANNEL2_ONE_SHOT);
+        data.write(count as u8);
+        data.write((count >> 8) as u8);
+        port_b.write(value | 0b01);
+        // 计数到0时OUT2变高
+        while port_b.read() & 0x20 == 0 {
+            core::hint::spin_loop();
+        }
+        port_b.write(value);
+    }
+}
+
+#[test_case]
+fn test_frequency_matches_configured() {
+    use super::TIMER_FREQUENCY_HZ;
+
+    // `init` 中已经按 `TIMER_FREQUENCY_HZ` 设置过，实际频率与之相差不到1%
+    let actual = frequency();
+    assert!(actual.abs_diff(TIMER_FREQUENCY_HZ) * 100 < TIMER_FREQUENCY_HZ);
+    // 100 Hz对应的周期约为10毫秒
+    assert!(period_ns().abs_diff(1_000_000_000 / u64::from(TIMER_FREQUENCY_HZ)) < 100_000);
+}
@@ -0,0 +1,297 @@
+// CMOS实时时钟(RTC, Motorola MC146818)
+// RTC由主板电池供电，关机后仍然走时，是启动时获取日期和时间的唯一来源。它的寄存器位于CMOS中，
+// 先向端口0x70写入寄存器号，再从端口0x71读写数据。需要注意：
+// - 数值可能是BCD编码也可能是二进制，小时可能是12小时制也可能是24小时制，由状态寄存器B决定；
+// - RTC每秒更新一次，更新期间(状态寄存器A第7位)读到的值可能不一致，所以要等更新结束，并连续读两次直到结果相同；
+// - 年份寄存器只有两位数，世纪保存在FADT指定的CMOS寄存器中(如果有)。
+// RTC还可以在IRQ8上产生周期中断(2 Hz到8192 Hz)和每秒一次的更新结束中断
+
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+
+use crate::acpi;
+use crate::interrupts::{register_irq, IrqError};
+
+// RTC中断所在的IRQ，即从片的第0条中断线
+const RTC_IRQ: u8 = 8;
+
+const INDEX_PORT: u16 = 0x70;
+const DATA_PORT: u16 = 0x71;
+
+// 时间和日期寄存器
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+// 状态寄存器A：第7位表示正在更新，低4位是周期中断的频率选择
+const REG_STATUS_A: u8 = 0x0a;
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 1 << 7;
+// 状态寄存器B：第1位为24小时制，第2位为二进制模式，第4位开启更新结束中断，第6位开启周期中断
+const REG_STATUS_B: u8 = 0x0b;
+const STATUS_B_24_HOUR: u8 = 1 << 1;
+const STATUS_B_BINARY: u8 = 1 << 2;
+const STATUS_B_UPDATE_INTERRUPT: u8 = 1 << 4;
+const STATUS_B_PERIODIC_INTERRUPT: u8 = 1 << 6;
+// 状态寄存器C：读取后清除，第4位表示更新结束中断，第6位表示周期中断
+const REG_STATUS_C: u8 = 0x0c;
+const STATUS_C_UPDATE: u8 = 1 << 4;
+const STATUS_C_PERIODIC: u8 = 1 << 6;
+// 12小时制下小时寄存器的第7位表示下午
+const HOUR_PM: u8 = 1 << 7;
+
+// 周期中断的频率为 32768 >> (rate - 1) Hz，rate的有效范围是3..=15
+const MIN_PERIODIC_HZ: u32 = 2;
+const MAX_PERIODIC_HZ: u32 = 8192;
+
+// 端口0x70和0x71需要成对访问，中断处理函数中直接加锁，加锁规则见 `interrupts::irq`
+static CMOS: Mutex<()> = Mutex::new(());
+// 收到的周期中断和更新结束中断的次数
+static PERIODIC_COUNT: AtomicU64 = AtomicU64::new(0);
+static UPDATE_COUNT: AtomicU64 = AtomicU64::new(0);
+
+// 日期和时间
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+// 按 `2024.08.02 13:05:09` 的格式输出
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}.{:02}.{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+// 从RTC寄存器中读出的原始值，还没有按状态寄存器B转换
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RawTime {
+    second: u8,
+    minute: u8,
+    hour: u8,
+    day: u8,
+    month: u8,
+    year: u8,
+    // FADT没有指定世纪寄存器时为 `None`
+    century: Option<u8>,
+}
+
+unsafe fn read_register(register: u8) -> u8 {
+    Port::<u8>::new(INDEX_PORT).write(register);
+    Port::<u8>::new(DATA_PORT).read()
+}
+
+unsafe fn write_register(register: u8, value: u8) {
+    Port::<u8>::new(INDEX_PORT).write(register);
+    Port::<u8>::new(DATA_PORT).write(value);
+}
+
+// 在关闭中断并持有CMOS锁的情况下执行 `f`
+fn with_cmos<R>(f: impl FnOnce() -> R) -> R {
+    interrupts::without_interrupts(|| {
+        let _guard = CMOS.lock();
+        f()
+    })
+}
+
+// 等待正在进行的更新结束，然后读出所有时间寄存器
+unsafe fn read_raw(century_register: Option<u8>) -> RawTime {
+    while read_register(REG_STATUS_A) & STATUS_A_UPDATE_IN_PROGRESS != 0 {
+        core::hint::spin_loop();
+    }
+    RawTime {
+        second: read_register(REG_SECONDS),
+        minute: read_register(REG_MINUTES),
+        hour: read_register(REG_HOURS),
+        day: read_register(REG_DAY),
+        month: read_register(REG_MONTH),
+        year: read_register(REG_YEAR),
+        century: century_register.map(|register| read_register(register)),
+    }
+}
+
+fn bcd_to_binary(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0f)
+}
+
+// 按状态寄存器B的格式把原始值转换成日期和时间
+// 没有世纪寄存器时认为是21世纪
+fn decode(raw: RawTime, status_b: u8) -> DateTime {
+    let binary = status_b & STATUS_B_BINARY != 0;
+    let convert = |value: u8| if binary { value } else { bcd_to_binary(value) };
+
+    // 12小时制下，下午的标志位在编码之外，要先去掉再转换。12点表示中午或午夜
+    let pm = status_b & STATUS_B_24_HOUR == 0 && raw.hour & HOUR_PM != 0;
+    let mut hour = convert(raw.hour & !HOUR_PM);
+    if status_b & STATUS_B_24_HOUR == 0 {
+        hour %= 12;
+        if pm {
+            hour += 12;
+        }
+    }
+
+    let century = raw.century.map_or(20, convert);
+    DateTime {
+        year: u16::from(century) * 100 + u16::from(convert(raw.year)),
+        month: convert(raw.month),
+        day: convert(raw.day),
+        hour,
+        minute: convert(raw.minute),
+        second: convert(raw.second),
+    }
+}
+
+// 当前的日期和时间(RTC中保存的本地时间，通常是UTC)
+// ACPI表已经读取时使用FADT中的世纪寄存器，否则认为是21世纪，所以在内存初始化之前也可以调用
+pub fn wall_clock() -> DateTime {
+    let century_register = acpi::tables()
+        .and_then(|acpi| acpi.fadt.as_ref())
+        .map(|fadt| fadt.century_register)
+        .filter(|&register| register != 0);
+    with_cmos(|| unsafe {
+        // 两次读取之间可能恰好发生了一次更新，结果相同才说明读到的值是一致的
+        let mut raw = read_raw(century_register);
+        loop {
+            let again = read_raw(century_register);
+            if again == raw {
+                break;
+            }
+            raw = again;
+        }
+        decode(raw, read_register(REG_STATUS_B))
+    })
+}
+
+// 开启RTC的周期中断，频率取不超过 `hz` 的2的幂(2 Hz到8192 Hz)，返回实际的频率
+pub fn enable_periodic_interrupt(hz: u32) -> u32 {
+    let hz = hz.clamp(MIN_PERIODIC_HZ, MAX_PERIODIC_HZ);
+    // hz向下取整到2的幂，32768 >> (rate - 1) == hz
+    let rate = 16 - (31 - hz.leading_zeros()) as u8;
+    with_cmos(|| unsafe {
+        let status_a = read_register(REG_STATUS_A);
+        write_register(REG_STATUS_A, (status_a & 0xf0) | rate);
+    });
+    set_interrupt_enable(STATUS_B_PERIODIC_INTERRUPT, true);
+    32768 >> (rate - 1)
+}
+
+// 开启每秒一次的更新结束中断
+pub fn enable_update_interrupt() {
+    set_interrupt_enable(STATUS_B_UPDATE_INTERRUPT, true);
+}
+
+// 关闭RTC的周期中断和更新结束中断
+pub fn disable_interrupts() {
+    set_interrupt_enable(STATUS_B_PERIODIC_INTERRUPT | STATUS_B_UPDATE_INTERRUPT, false);
+}
+
+fn set_interrupt_enable(bits: u8, enable: bool) {
+    with_cmos(|| unsafe {
+        let status_b = read_register(REG_STATUS_B);
+        let status_b = if enable { status_b | bits } else { status_b & !bits };
+        write_register(REG_STATUS_B, status_b);
+        // 清除可能已经挂起的中断标志，否则RTC不会产生新的中断
+        read_register(REG_STATUS_C);
+    });
+    if enable {
+        // 两种中断共用IRQ8，第二次开启时处理函数已经注册过了
+        match register_irq(RTC_IRQ, on_interrupt) {
+            Ok(()) | Err(IrqError::AlreadyRegistered) => {}
+            Err(err) => panic!("failed to register RTC IRQ: {:?}", err),
+        }
+    }
+}
+
+// 收到的周期中断次数
+pub fn periodic_interrupts() -> u64 {
+    PERIODIC_COUNT.load(Ordering::Relaxed)
+}
+
+// 收到的更新结束中断次数，开启后每秒加1
+pub fn update_interrupts() -> u64 {
+    UPDATE_COUNT.load(Ordering::Relaxed)
+}
+
+// IRQ8的中断处理函数：读取状态寄存器C确认中断，否则RTC不会再产生下一次中断，并按中断原因计数
+fn on_interrupt() {
+    let _guard = CMOS.lock();
+    let status_c = unsafe { read_register(REG_STATUS_C) };
+    if status_c & STATUS_C_PERIODIC != 0 {
+        PERIODIC_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+    if status_c & STATUS_C_UPDATE != 0 {
+        UPDATE_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test_case]
+fn test_decode_bcd_12_hour() {
+    // BCD编码、12小时制：下午1点05分09秒，2024年8月2日
+    let raw = RawTime {
+        second: 0x09,
+        minute: 0x05,
+        hour: HOUR_PM | 0x01,
+        day: 0x02,
+        month: 0x08,
+        year: 0x24,
+        century: Some(0x20),
+    };
+    let time = decode(raw, 0);
+    assert_eq!(
+        time,
+        DateTime { year: 2024, month: 8, day: 2, hour: 13, minute: 5, second: 9 }
+    );
+    // 上午12点是午夜
+    assert_eq!(decode(RawTime { hour: 0x12, ..raw }, 0).hour, 0);
+    assert_eq!(decode(RawTime { hour: HOUR_PM | 0x12, ..raw }, 0).hour, 12);
+}
+
+#[test_case]
+fn test_decode_binary_24_hour() {
+    let raw = RawTime {
+        second: 59,
+        minute: 30,
+        hour: 23,
+        day: 31,
+        month: 12,
+        year: 99,
+        century: None,
+    };
+    let time = decode(raw, STATUS_B_BINARY | STATUS_B_24_HOUR);
+    assert_eq!(
+        time,
+        DateTime { year: 2099, month: 12, day: 31, hour: 23, minute: 30, second: 59 }
+    );
+    assert_eq!(alloc::format!("{}", time), "2099.12.31 23:30:59");
+}
+
+#[test_case]
+fn test_wall_clock_is_valid() {
+    let time = wall_clock();
+    assert!(time.year >= 2020);
+    assert!((1..=12).contains(&time.month) && (1..=31).contains(&time.day));
+    assert!(time.hour < 24 && time.minute < 60 && time.second < 60);
+}
+
+#[test_case]
+fn test_periodic_interrupt() {
+    assert_eq!(enable_periodic_interrupt(1000), 512);
+    let start = periodic_interrupts();
+    super::sleep_ms(50);
+    disable_interrupts();
+    // 50毫秒内应收到约25次中断
+    assert!(periodic_interrupts() - start >= 10);
+}
@@ -0,0 +1,88 @@
+// 时间戳计数器(TSC)
+// `rdtsc` 指令读取一个随CPU时钟递增的64位计数器，读取只需要几十个周期，是最便宜的高精度时间来源。
+// 它的频率没有标准的查询方法，所以启动时用频率已知的时钟(HPET，不可用时用PIT)测量一段时间内TSC的增量来校准
+
+use core::arch::x86_64::_rdtsc;
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::instructions::interrupts;
+
+use super::{hpet, pit};
+
+// 校准时测量的时间长度(毫秒)，需要小于PIT通道2单次计时的上限(约54毫秒)
+const CALIBRATION_MS: u32 = 10;
+
+// TSC的频率(Hz)，0表示还没有校准
+static FREQUENCY_HZ: AtomicU64 = AtomicU64::new(0);
+// 校准完成时的TSC值和当时的开机时间(纳秒)，`nanos` 以此为起点换算
+static BASE_TSC: AtomicU64 = AtomicU64::new(0);
+static BASE_NS: AtomicU64 = AtomicU64::new(0);
+
+// 校准TSC时使用的参考时钟
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reference {
+    Hpet,
+    Pit,
+}
+
+// 读取TSC
+pub fn read() -> u64 {
+    unsafe { _rdtsc() }
+}
+
+// TSC的频率(Hz)，没有校准时返回 `None`
+pub fn frequency() -> Option<u64> {
+    match FREQUENCY_HZ.load(Ordering::Acquire) {
+        0 => None,
+        hz => Some(hz),
+    }
+}
+
+// 以HPET为参考校准TSC，HPET不可用时改用PIT通道2，返回实际使用的参考时钟
+// 测量期间关闭中断，避免中断处理拉长其中一方的读数
+pub fn calibrate() -> Reference {
+    let (reference, hz) = interrupts::without_interrupts(|| match hpet::get() {
+        Some(hpet) => {
+            let hpet_start = hpet.counter();
+            let tsc_start = read();
+            hpet.spin_wait(core::time::Duration::from_millis(u64::from(CALIBRATION_MS)));
+            let tsc_end = read();
+            let elapsed = hpet.elapsed(hpet_start, hpet.counter());
+            let hz = u128::from(tsc_end - tsc_start) * 1_000_000_000 / elapsed.as_nanos().max(1);
+            (Reference::Hpet, hz as u64)
+        }
+        None => {
+            let tsc_start = read();
+            pit::spin_wait_ms(CALIBRATION_MS);
+            let tsc_end = read();
+            (Reference::Pit, (tsc_end - tsc_start) * 1000 / u64::from(CALIBRATION_MS))
+        }
+    });
+    BASE_NS.store(super::uptime().as_nanos() as u64, Ordering::Relaxed);
+    BASE_TSC.store(read(), Ordering::Relaxed);
+    // 最后写入频率，读到非0频率的一方一定也能看到上面的起点
+    FREQUENCY_HZ.store(hz.max(1), Ordering::Release);
+    reference
+}
+
+// 由TSC换算得到的开机以来的纳秒数，没有校准时返回 `None`
+pub fn nanos() -> Option<u64> {
+    let hz = FREQUENCY_HZ.load(Ordering::Acquire);
+    if hz == 0 {
+        return None;
+    }
+    let cycles = read().saturating_sub(BASE_TSC.load(Ordering::Relaxed));
+    Some(BASE_NS.load(Ordering::Relaxed) + cycles_to_ns(cycles, hz))
+}
+
+// 把TSC的计数换算成纳秒
+pub fn cycles_to_ns(cycles: u64, frequency_hz: u64) -> u64 {
+    (u128::from(cycles) * 1_000_000_000 / u128::from(frequency_hz)) as u64
+}
+
+#[test_case]
+fn test_cycles_to_ns() {
+    assert_eq!(cycles_to_ns(3_000_000_000, 3_000_000_000), 1_000_000_000);
+    assert_eq!(cycles_to_ns(3, 3_000_000_000), 1);
+    // 中间结果超过u64也不会溢出
+    assert_eq!(cycles_to_ns(u64::MAX, 1_000_000_000), u64::MAX);
+}
@@ -0,0 +1,323 @@
+// ANSI/VT100转义序列解析器
+// 逐个字符输入，按状态机识别 `ESC [ 参数 最终字节` 形式的控制序列(CSI)以及 `ESC 7`/`ESC 8`，
+// 识别出的序列转换成 `Action` 交给 `Writer` 执行。普通字符原样作为 `Action::Print` 返回，
+// 不认识的序列被完整地吞掉，不会在屏幕上留下乱码。解析器不使用堆，在堆初始化之前也可以工作
+
+const ESC: u8 = 0x1b;
+// CAN和SUB会中止正在解析的序列
+const CAN: u8 = 0x18;
+const SUB: u8 = 0x1a;
+
+// 一个序列最多保存的参数个数，多余的参数被忽略
+pub const MAX_PARAMS: usize = 8;
+// 参数值的上限，防止溢出
+const MAX_PARAM_VALUE: u16 = 9999;
+
+// 控制序列的数字参数。省略的参数记为0，由各个序列决定0的含义
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Params {
+    values: [u16; MAX_PARAMS],
+    len: usize,
+    // 参数个数已经超过 `MAX_PARAMS`，之后的数字都被丢弃
+    overflowed: bool,
+}
+
+impl Params {
+    const fn new() -> Self {
+        Params {
+            values: [0; MAX_PARAMS],
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    // 第 `index` 个参数，省略时为0
+    pub fn get(&self, index: usize) -> u16 {
+        if index < self.len {
+            self.values[index]
+        } else {
+            0
+        }
+    }
+
+    // 作为次数使用的参数：省略或为0时表示1
+    fn count(&self, index: usize) -> usize {
+        usize::from(self.get(index).max(1))
+    }
+
+    // 所有参数。`ESC [ m` 这样没有参数的序列相当于一个0
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        self.values[..self.len.max(1)].iter().copied()
+    }
+}
+
+// 擦除的范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EraseMode {
+    // 从光标到行尾(或屏幕末尾)
+    ToEnd,
+    // 从行首(或屏幕开头)到光标
+    ToStart,
+    // 整行(或整个屏幕)
+    All,
+}
+
+impl EraseMode {
+    fn from_param(param: u16) -> Option<Self> {
+        match param {
+            0 => Some(EraseMode::ToEnd),
+            1 => Some(EraseMode::ToStart),
+            // 3表示连同回滚缓冲区一起清除，这里等同于2
+            2 | 3 => Some(EraseMode::All),
+            _ => None,
+        }
+    }
+}
+
+// 解析出的动作。行列号从0开始
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    // 普通字符，包括换行等控制字符
+    Print(char),
+    // SGR(`ESC [ ... m`)：设置颜色和显示属性
+    SetGraphics(Params),
+    // CUP(`ESC [ 行 ; 列 H`)：移动光标到指定位置
+    CursorPosition { row: usize, column: usize },
+    // CUU/CUD/CUF/CUB(`ESC [ n A/B/C/D`)：光标向上/下/右/左移动n格
+    CursorUp(usize),
+    CursorDown(usize),
+    CursorForward(usize),
+    CursorBack(usize),
+    // EL(`ESC [ n K`)：擦除行
+    EraseInLine(EraseMode),
+    // ED(`ESC [ n J`)：擦除屏幕
+    EraseInDisplay(EraseMode),
+    // `ESC 7` 或 `ESC [ s`：保存光标位置和颜色
+    SaveCursor,
+    // `ESC 8` 或 `ESC [ u`：恢复保存的光标位置和颜色
+    RestoreCursor,
+    // DECTCEM(`ESC [ ? 25 h/l`)：显示或隐藏光标
+    ShowCursor(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    // 普通文本
+    Ground,
+    // 收到了ESC
+    Escape,
+    // 收到了 `ESC [`，正在读取参数
+    Csi,
+}
+
+pub struct Parser {
+    state: State,
+    params: Params,
+    // 以 `?` 等字符开头的私有序列(如 `ESC [ ? 25 l`)
+    private: bool,
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Parser {
+            state: State::Ground,
+            params: Params::new(),
+            private: false,
+        }
+    }
+
+    // 输入一个字符，完成一个动作时返回它
+    pub fn advance(&mut self, c: char) -> Option<Action> {
+        // 转义序列只由ASCII字符组成。非ASCII字符在普通状态下直接打印，出现在序列中间时放弃整个序列
+        if !c.is_ascii() {
+            if self.state == State::Ground {
+                return Some(Action::Print(c));
+            }
+            self.state = State::Ground;
+            return None;
+        }
+        let byte = c as u8;
+        match (self.state, byte) {
+            // 任何状态下收到ESC都重新开始一个序列
+            (_, ESC) => {
+                self.state = State::Escape;
+                None
+            }
+            (State::Ground, _) => Some(Action::Print(c)),
+            (_, CAN) | (_, SUB) => {
+                self.state = State::Ground;
+                None
+            }
+            (State::Escape, b'[') => {
+                self.state = State::Csi;
+                self.params = Params::new();
+                self.private = false;
+                None
+            }
+            (State::Escape, byte) => {
+                self.state = State::Ground;
+                match byte {
+                    b'7' => Some(Action::SaveCursor),
+                    b'8' => Some(Action::RestoreCursor),
+                    _ => None,
+                }
+            }
+            (State::Csi, byte) => self.csi(byte),
+        }
+    }
+
+    fn csi(&mut self, byte: u8) -> Option<Action> {
+        match byte {
+            b'0'..=b'9' if self.params.overflowed => None,
+            b'0'..=b'9' => {
+                if self.params.len == 0 {
+                    self.params.len = 1;
+                }
+                let value = &mut self.params.values[self.params.len - 1];
+                let next = u32::from(*value) * 10 + u32::from(byte - b'0');
+                *value = next.min(u32::from(MAX_PARAM_VALUE)) as u16;
+                None
+            }
+            b';' => {
+                // 第一个参数被省略时(`ESC [ ; 5 H`)也要占一个位置
+                let len = self.params.len.max(1);
+                if len == MAX_PARAMS {
+                    self.params.overflowed = true;
+                } else {
+                    self.params.len = len + 1;
+                }
+                None
+            }
+            // 参数开头的 `<=>?` 表示私有序列
+            b'<'..=b'?' => {
+                self.private = true;
+                None
+            }
+            // 中间字节，这里用不到
+            0x20..=0x2f => None,
+            // 最终字节，序列结束
+            0x40..=0x7e => {
+                self.state = State::Ground;
+                if self.private {
+                    self.dispatch_private(byte)
+                } else {
+                    self.dispatch(byte)
+                }
+            }
+            // 序列中出现的其他控制字符不合法，放弃整个序列
+            _ => {
+                self.state = State::Ground;
+                None
+            }
+        }
+    }
+
+    fn dispatch(&self, final_byte: u8) -> Option<Action> {
+        let params = &self.params;
+        match final_byte {
+            b'm' => Some(Action::SetGraphics(*params)),
+            b'H' | b'f' => Some(Action::CursorPosition {
+                row: params.count(0) - 1,
+                column: params.count(1) - 1,
+            }),
+            b'A' => Some(Action::CursorUp(params.count(0))),
+            b'B' => Some(Action::CursorDown(params.count(0))),
+            b'C' => Some(Action::CursorForward(params.count(0))),
+            b'D' => Some(Action::CursorBack(params.count(0))),
+            b'K' => EraseMode::from_param(params.get(0)).map(Action::EraseInLine),
+            b'J' => EraseMode::from_param(params.get(0)).map(Action::EraseInDisplay),
+            b's' => Some(Action::SaveCursor),
+            b'u' => Some(Action::RestoreCursor),
+            _ => None,
+        }
+    }
+
+    // 私有序列中只支持显示/隐藏光标
+    fn dispatch_private(&self, final_byte: u8) -> Option<Action> {
+        match (self.params.get(0), final_byte) {
+            (25, b'h') => Some(Action::ShowCursor(true)),
+            (25, b'l') => Some(Action::ShowCursor(false)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+fn parse(input: &str) -> alloc::vec::Vec<Action> {
+    let mut parser = Parser::new();
+    input.chars().filter_map(|c| parser.advance(c)).collect()
+}
+
+#[test_case]
+fn test_parse_plain_text() {
+    assert_eq!(parse("a\n"), [Action::Print('a'), Action::Print('\n')]);
+}
+
+#[test_case]
+fn test_parse_non_ascii() {
+    assert_eq!(parse("é─"), [Action::Print('é'), Action::Print('─')]);
+    // 序列中间的非ASCII字符使整个序列作废，之后的字符正常打印
+    assert_eq!(parse("\x1b[3é1mx"), [Action::Print('1'), Action::Print('m'), Action::Print('x')]);
+}
+
+#[test_case]
+fn test_parse_sgr() {
+    let actions = parse("\x1b[1;31mX\x1b[m");
+    assert_eq!(actions.len(), 3);
+    match actions[0] {
+        Action::SetGraphics(params) => assert!(params.iter().eq([1, 31])),
+        other => panic!("unexpected action {:?}", other),
+    }
+    assert_eq!(actions[1], Action::Print('X'));
+    // 没有参数的SGR相当于 `ESC [ 0 m`
+    match actions[2] {
+        Action::SetGraphics(params) => assert!(params.iter().eq([0])),
+        other => panic!("unexpected action {:?}", other),
+    }
+}
+
+#[test_case]
+fn test_parse_cursor_and_erase() {
+    assert_eq!(
+        parse("\x1b[5;10H\x1b[;3H\x1b[A\x1b[2B\x1b[0C\x1b[4D"),
+        [
+            Action::CursorPosition { row: 4, column: 9 },
+            Action::CursorPosition { row: 0, column: 2 },
+            Action::CursorUp(1),
+            Action::CursorDown(2),
+            Action::CursorForward(1),
+            Action::CursorBack(4),
+        ]
+    );
+    assert_eq!(
+        parse("\x1b[K\x1b[1K\x1b[2J\x1b7\x1b[s\x1b8\x1b[u"),
+        [
+            Action::EraseInLine(EraseMode::ToEnd),
+            Action::EraseInLine(EraseMode::ToStart),
+            Action::EraseInDisplay(EraseMode::All),
+            Action::SaveCursor,
+            Action::SaveCursor,
+            Action::RestoreCursor,
+            Action::RestoreCursor,
+        ]
+    );
+}
+
+#[test_case]
+fn test_parse_ignores_unknown_sequences() {
+    // 不认识的私有序列和最终字节，以及被CAN中止的序列都不产生输出
+    assert_eq!(parse("\x1b[?1049h\x1b[5n\x1b[3\x18a"), [Action::Print('a')]);
+    assert_eq!(
+        parse("\x1b[?25l\x1b[?25h"),
+        [Action::ShowCursor(false), Action::ShowCursor(true)]
+    );
+}
+
+#[test_case]
+fn test_parse_ignores_extra_params() {
+    // 第9个参数被丢弃，不会接在第8个参数后面
+    match parse("\x1b[1;1;1;1;1;1;1;4;4m")[..] {
+        [Action::SetGraphics(params)] => assert!(params.iter().eq([1, 1, 1, 1, 1, 1, 1, 4])),
+        ref other => panic!("unexpected actions {:?}", other),
+    }
+}
@@ -0,0 +1,149 @@
+// 虚拟控制台
+// 每个控制台有自己的输出器：光标位置、颜色、转义序列解析状态和回滚历史互不影响。
+// 同一时刻只有一个控制台(活动控制台)的输出器写入VGA缓冲区，其他控制台写入各自的后台缓冲区。
+// 切换时把VGA的内容保存到旧控制台的后台缓冲区，再把新控制台的后台缓冲区复制到VGA，然后交换两者手里的缓冲区引用
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+use super::{Buffer, ScreenChar, Writer, BUFFER_HEIGHT, BUFFER_WIDTH};
+
+// 虚拟控制台的数量，对应Alt+F1到Alt+F6
+pub const CONSOLE_COUNT: usize = 6;
+// 内核日志所在的控制台，`print!`/`println!` 的输出写到这里，开机时处于前台
+pub const KERNEL_CONSOLE: usize = 0;
+
+// VGA文本缓冲区的物理地址，bootloader把它恒等映射到同样的虚拟地址
+const VGA_BUFFER_ADDRESS: usize = 0xb8000;
+
+// 每个控制台一个后台缓冲区
+// `Buffer` 是 `repr(transparent)` 的 `Volatile<ScreenChar>` 二维数组，而 `Volatile` 也是 `repr(transparent)`，
+// 所以和 `ScreenChar` 二维数组的内存布局相同，可以直接转换。`Volatile::new` 不是 `const fn`，不能直接写成 `Buffer` 的静态变量
+static mut BACKING: [[[ScreenChar; BUFFER_WIDTH]; BUFFER_HEIGHT]; CONSOLE_COUNT] =
+    [[[ScreenChar::BLANK; BUFFER_WIDTH]; BUFFER_HEIGHT]; CONSOLE_COUNT];
+
+// 当前活动控制台的下标
+static ACTIVE: AtomicUsize = AtomicUsize::new(KERNEL_CONSOLE);
+
+// 第 `index` 个后台缓冲区
+/// # Safety
+///
+/// 调用者必须保证每个下标只调用一次，否则会出现指向同一个缓冲区的多个 `&mut` 引用
+unsafe fn backing(index: usize) -> &'static mut Buffer {
+    &mut *(core::ptr::addr_of_mut!(BACKING[index]) as *mut Buffer)
+}
+
+lazy_static! {
+    // 所有控制台的输出器。内核控制台一开始就在前台，直接使用VGA缓冲区，其余的使用各自的后台缓冲区
+    static ref CONSOLES: [Mutex<Writer>; CONSOLE_COUNT] = core::array::from_fn(|index| {
+        let buffer = if index == KERNEL_CONSOLE {
+            unsafe { &mut *(VGA_BUFFER_ADDRESS as *mut Buffer) }
+        } else {
+            unsafe { backing(index) }
+        };
+        Mutex::new(Writer::new(buffer, index == KERNEL_CONSOLE))
+    });
+
+    // 活动控制台暂时用不到的后台缓冲区，切换时和VGA缓冲区轮换
+    // 同时也是切换用的锁：持有它才能修改 `ACTIVE`，所以同一时刻只会有一次切换
+    static ref SPARE: Mutex<&'static mut Buffer> = Mutex::new(unsafe { backing(KERNEL_CONSOLE) });
+}
+
+// 第 `index` 个控制台的输出器
+// 在线程上下文中加锁时需要关闭中断，防止中断处理函数里打印时死锁
+pub fn console(index: usize) -> &'static Mutex<Writer> {
+    &CONSOLES[index]
+}
+
+// 当前显示在屏幕上的控制台的下标
+pub fn active_console() -> usize {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+// 把第 `index` 个控制台切换到前台
+pub fn switch_console(index: usize) {
+    assert!(index < CONSOLE_COUNT, "console index {} out of range", index);
+
+    interrupts::without_interrupts(|| {
+        let mut spare = SPARE.lock();
+        let current = ACTIVE.load(Ordering::Relaxed);
+        if index == current {
+            return;
+        }
+        // 其他地方每次只锁一个控制台，所以这里同时锁住两个不会死锁
+        swap_active(&mut spare, &mut CONSOLES[current].lock(), &mut CONSOLES[index].lock());
+        ACTIVE.store(index, Ordering::Relaxed);
+    });
+}
+
+// 在panic处理函数中把第 `index` 个控制台切换到前台，不等待任何锁
+// 切换用的锁或者两个控制台中任何一个正被占用时放弃切换，控制台留在原来的状态
+pub(super) fn try_switch_console(index: usize) {
+    let Some(mut spare) = SPARE.try_lock() else {
+        return;
+    };
+    let current = ACTIVE.load(Ordering::Relaxed);
+    if index == current {
+        return;
+    }
+    if let (Some(mut old), Some(mut new)) = (CONSOLES[current].try_lock(), CONSOLES[index].try_lock()) {
+        swap_active(&mut spare, &mut old, &mut new);
+        ACTIVE.store(index, Ordering::Relaxed);
+    }
+}
+
+// 把 `old` 的画面保存到后台缓冲区，把 `new` 的画面显示到VGA，`spare` 是当前空闲的后台缓冲区
+fn swap_active(spare: &mut &'static mut Buffer, old: &mut Writer, new: &mut Writer) {
+    // 翻看历史时VGA上显示的不是实时画面，先恢复再保存
+    old.snap_back();
+    copy_buffer(old.buffer, spare);
+    core::mem::swap(&mut old.buffer, spare);
+    old.active = false;
+
+    // 此时 `spare` 是VGA缓冲区
+    copy_buffer(new.buffer, spare);
+    core::mem::swap(&mut new.buffer, spare);
+    new.active = true;
+    new.restore_cursor();
+}
+
+fn copy_buffer(from: &Buffer, to: &mut Buffer) {
+    for (from_row, to_row) in from.chars.iter().zip(to.chars.iter_mut()) {
+        for (from_char, to_char) in from_row.iter().zip(to_row.iter_mut()) {
+            to_char.write(from_char.read());
+        }
+    }
+}
+
+#[test_case]
+fn test_switch_console() {
+    use core::fmt::Write;
+
+    let vga = unsafe { &*(VGA_BUFFER_ADDRESS as *const Buffer) };
+    let first_char = || vga.chars[0][0].read().ascii_character;
+
+    // 后台控制台的输出写到它自己的后台缓冲区，切换过来后才显示
+    interrupts::without_interrupts(|| {
+        write!(console(1).lock(), "\x1b[Hconsole one").expect("write failed");
+    });
+
+    switch_console(1);
+    assert_eq!(active_console(), 1);
+    assert_eq!(first_char(), b'c');
+
+    // 内核控制台转到后台后，它的输出也不会出现在屏幕上。保存并恢复光标，不影响后面的测试
+    interrupts::without_interrupts(|| {
+        write!(console(KERNEL_CONSOLE).lock(), "\x1b7\x1b[Hkernel\x1b8").expect("write failed");
+    });
+    assert_eq!(first_char(), b'c');
+
+    switch_console(KERNEL_CONSOLE);
+    assert_eq!(active_console(), KERNEL_CONSOLE);
+    assert_eq!(first_char(), b'k');
+    // 控制台1的内容保存在它的后台缓冲区里
+    interrupts::without_interrupts(|| {
+        assert_eq!(console(1).lock().buffer.chars[0][0].read().ascii_character, b'c');
+    });
+}
@@ -0,0 +1,76 @@
+// Unicode到代码页437(CP437)的转换
+// VGA文本模式的字库是IBM PC的CP437：0x20到0x7e与ASCII相同，0x01到0x1f和0x7f是笑脸、箭头等符号，
+// 0x80到0xff是带重音的拉丁字母、制表符、希腊字母和数学符号。这里把Unicode字符映射到对应的字形编号
+
+// 没有对应字形的字符显示为 `■`
+pub const REPLACEMENT: u8 = 0xfe;
+
+// 0x01到0x1f的字形
+const LOW: [char; 31] = [
+    '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼', //
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+];
+
+// 0x7f的字形
+const HOUSE: char = '⌂';
+
+// 0x80到0xff的字形，每行16个
+const HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+// 字库里没有、但有外形相近的字形可以代替的字符
+const ALIASES: [(char, u8); 9] = [
+    // 希腊字母β和μ与ß、µ共用字形，欧姆符号与Ω共用
+    ('β', 0xe1),
+    ('\u{3bc}', 0xe6),
+    ('\u{2126}', 0xea),
+    ('∅', 0xed),
+    ('∈', 0xee),
+    // 圆角制表符用直角的代替
+    ('╭', 0xda),
+    ('╮', 0xbf),
+    ('╯', 0xd9),
+    ('╰', 0xc0),
+];
+
+// 把字符转换为CP437的字形编号，字库里没有时返回 `None`
+// 换行、制表等ASCII控制字符不是字形，也返回 `None`，由调用者自己处理
+pub fn encode(c: char) -> Option<u8> {
+    if matches!(c, ' '..='~') {
+        return Some(c as u8);
+    }
+    if c == HOUSE {
+        return Some(0x7f);
+    }
+    if let Some(index) = LOW.iter().position(|&glyph| glyph == c) {
+        return Some(index as u8 + 0x01);
+    }
+    if let Some(index) = HIGH.iter().position(|&glyph| glyph == c) {
+        return Some(index as u8 + 0x80);
+    }
+    ALIASES.iter().find(|&&(alias, _)| alias == c).map(|&(_, glyph)| glyph)
+}
+
+#[test_case]
+fn test_encode_cp437() {
+    assert_eq!(encode('A'), Some(b'A'));
+    assert_eq!(encode('é'), Some(0x82));
+    assert_eq!(encode('┌'), Some(0xda));
+    assert_eq!(encode('─'), Some(0xc4));
+    assert_eq!(encode('╬'), Some(0xce));
+    assert_eq!(encode('Σ'), Some(0xe4));
+    assert_eq!(encode('☺'), Some(0x01));
+    assert_eq!(encode('⌂'), Some(0x7f));
+    assert_eq!(encode('╭'), Some(0xda));
+    // 控制字符和字库里没有的字符
+    assert_eq!(encode('\n'), None);
+    assert_eq!(encode('中'), None);
+}
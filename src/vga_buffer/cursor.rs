@@ -0,0 +1,76 @@
+// VGA硬件光标
+// 文本模式下闪烁的光标由CRT控制器(CRTC)绘制，与 `Writer` 记录的写入位置无关，需要把位置写进CRTC的寄存器它才会移动。
+// CRTC的寄存器通过一对端口访问：先向0x3D4写入寄存器号，再读写0x3D5
+
+use x86_64::instructions::port::Port;
+
+const CRTC_INDEX_PORT: u16 = 0x3d4;
+const CRTC_DATA_PORT: u16 = 0x3d5;
+
+// 字符单元的高度(扫描线数减1)在低5位
+const REG_MAX_SCAN_LINE: u8 = 0x09;
+// 光标开始的扫描线在低5位，第5位为1时隐藏光标
+const REG_CURSOR_START: u8 = 0x0a;
+const CURSOR_DISABLE: u8 = 1 << 5;
+// 光标结束的扫描线在低5位
+const REG_CURSOR_END: u8 = 0x0b;
+// 光标位置(字符下标 `行 * 宽度 + 列`)的高8位和低8位
+const REG_LOCATION_HIGH: u8 = 0x0e;
+const REG_LOCATION_LOW: u8 = 0x0f;
+
+// 光标的形状
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    // 字符底部的两条扫描线，VGA的默认形状
+    Underline,
+    // 覆盖整个字符单元
+    Block,
+}
+
+unsafe fn read_register(register: u8) -> u8 {
+    Port::<u8>::new(CRTC_INDEX_PORT).write(register);
+    Port::<u8>::new(CRTC_DATA_PORT).read()
+}
+
+unsafe fn write_register(register: u8, value: u8) {
+    Port::<u8>::new(CRTC_INDEX_PORT).write(register);
+    Port::<u8>::new(CRTC_DATA_PORT).write(value);
+}
+
+// 把光标移动到字符下标 `index` 处
+pub(super) fn set_position(index: u16) {
+    unsafe {
+        write_register(REG_LOCATION_HIGH, (index >> 8) as u8);
+        write_register(REG_LOCATION_LOW, index as u8);
+    }
+}
+
+// 光标当前所在的字符下标，测试中用来确认寄存器确实被写入
+#[cfg(test)]
+pub(super) fn position() -> u16 {
+    unsafe { u16::from(read_register(REG_LOCATION_HIGH)) << 8 | u16::from(read_register(REG_LOCATION_LOW)) }
+}
+
+// 以 `shape` 形状显示光标。扫描线的范围按字符单元的实际高度计算
+pub(super) fn show(shape: CursorShape) {
+    unsafe {
+        let last_line = read_register(REG_MAX_SCAN_LINE) & 0x1f;
+        let (start, end) = match shape {
+            CursorShape::Underline => (last_line.saturating_sub(1), last_line),
+            CursorShape::Block => (0, last_line),
+        };
+        // 两个寄存器的高位有其他用途，只修改低5位
+        write_register(REG_CURSOR_START, (read_register(REG_CURSOR_START) & 0xc0) | start);
+        write_register(REG_CURSOR_END, (read_register(REG_CURSOR_END) & 0xe0) | end);
+    }
+}
+
+pub(super) fn hide() {
+    unsafe { write_register(REG_CURSOR_START, read_register(REG_CURSOR_START) | CURSOR_DISABLE) };
+}
+
+// 光标当前是否可见
+#[cfg(test)]
+pub(super) fn is_visible() -> bool {
+    unsafe { read_register(REG_CURSOR_START) & CURSOR_DISABLE == 0 }
+}
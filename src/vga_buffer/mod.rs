@@ -1,12 +1,24 @@
 // 引入Rust的格式化模块，用于输出显示
 use core::fmt;
 // 引入写接口，使得可以使用write!宏来打印
-use lazy_static::lazy_static;
-use spin::Mutex;
 // 引入`Volatile`类型封装内存，确保每次修改都是直接对硬件的
 use volatile::Volatile;
 use x86_64::instructions::interrupts;
 
+use ansi::{Action, EraseMode, Params};
+pub use console::{active_console, console, switch_console, CONSOLE_COUNT, KERNEL_CONSOLE};
+pub use cursor::CursorShape;
+
+// ANSI/VT100转义序列的解析
+mod ansi;
+// Unicode字符到VGA字库(代码页437)的转换
+mod cp437;
+// 多个虚拟控制台及其切换
+mod console;
+// CRTC控制的硬件光标
+mod cursor;
+// 滚出屏幕的历史行
+mod scrollback;
 
 // VGA标准颜色
 // 允许未使用代码不被警告
@@ -34,13 +46,60 @@ pub enum Color {
     White = 15,
 }
 
+impl Color {
+    // 按编号排列的16种颜色，前8种的编号加8就是对应的亮色
+    const ALL: [Color; 16] = [
+        Color::Black,
+        Color::Blue,
+        Color::Green,
+        Color::Cyan,
+        Color::Red,
+        Color::Magenta,
+        Color::Brown,
+        Color::LightGray,
+        Color::DarkGray,
+        Color::LightBlue,
+        Color::LightGreen,
+        Color::LightCyan,
+        Color::LightRed,
+        Color::Pink,
+        Color::Yellow,
+        Color::White,
+    ];
+
+    // ANSI颜色编号(0-7：黑、红、绿、黄、蓝、品红、青、白)对应的VGA颜色，`bright` 为真时取亮色
+    fn from_ansi(index: u16, bright: bool) -> Color {
+        const ANSI_TO_VGA: [Color; 8] = [
+            Color::Black,
+            Color::Red,
+            Color::Green,
+            Color::Brown,
+            Color::Blue,
+            Color::Magenta,
+            Color::Cyan,
+            Color::LightGray,
+        ];
+        let color = ANSI_TO_VGA[usize::from(index & 7)];
+        if bright {
+            color.bright()
+        } else {
+            color
+        }
+    }
+
+    // 对应的亮色，亮色保持不变
+    fn bright(self) -> Color {
+        Color::ALL[usize::from(self as u8 | 8)]
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 // 表示在内存中该结构体会像其单一字段那样布局，有助于避免布局问题和提高性能
 #[repr(transparent)]
 struct ColorCode(u8);
 
 impl ColorCode {
-    fn new(foreground: Color, bcakground: Color) -> ColorCode {
+    const fn new(foreground: Color, bcakground: Color) -> ColorCode {
         // 创建一个新的ColorCode实例。前景色放在低4位，背景色放在高4位，并转换为u8类型进行按位运算后返回
         ColorCode((bcakground as u8) << 4 | (foreground as u8))
     }
@@ -58,75 +117,317 @@ struct ScreenChar {
     color_code: ColorCode,
 }
 
+impl ScreenChar {
+    // 默认颜色的空格，后台缓冲区的初始内容
+    const BLANK: ScreenChar = ScreenChar {
+        ascii_character: b' ',
+        color_code: ColorCode::new(DEFAULT_FOREGROUND, DEFAULT_BACKGROUND),
+    };
+}
+
+// 默认的前景色和背景色，SGR 0(`ESC [ 0 m`)恢复到这组颜色
+const DEFAULT_FOREGROUND: Color = Color::LightCyan;
+const DEFAULT_BACKGROUND: Color = Color::Black;
+
 const BUFFER_HEIGHT: usize = 25;
 const BUFFER_WIDTH: usize = 80;
 // 定义Tab键对应空格数
 const TAB_SIZE: usize = 4;
+// 回滚缓冲区最多保存的行数，每行160字节。内核日志所在的控制台保存4000行(约625 KiB)，
+// 其他控制台只用来交互，各保存1000行(约156 KiB)，全部写满时共约1.4 MiB。缓冲区按两倍逐步增长到上限，
+// 扩容时新旧两块空间同时存在，内核控制台从2048行扩到4000行的瞬间最多再多占约320 KiB，
+// 总共仍不到4 MiB内核堆的一半。没怎么用过的控制台不会占用多少内核堆
+const SCROLLBACK_LINES: usize = 4000;
+const CONSOLE_SCROLLBACK_LINES: usize = 1000;
+// Shift+PageUp/PageDown每次翻动的行数，保留一行上一屏的内容便于衔接
+const SCROLL_PAGE: isize = BUFFER_HEIGHT as isize - 1;
 
-// 表示 VGA 文本模式下屏幕的整个字符缓冲区
+// 表示 VGA 文本模式下屏幕的整个字符缓冲区，后台控制台的缓冲区也使用这个类型
 #[repr(transparent)]
 struct Buffer {
     // 使用二维数组代表屏幕每个位置的字符信息，并包裹在Volatile内以防止编译器优化掉直接写入操作
     chars: [[Volatile<ScreenChar>; BUFFER_WIDTH]; BUFFER_HEIGHT],
 }
 
-// 输出器
+// 文字属性：前景色、背景色和粗体。粗体用亮色的前景色表示
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Attributes {
+    foreground: Color,
+    background: Color,
+    bold: bool,
+}
+
+impl Attributes {
+    const DEFAULT: Attributes = Attributes {
+        foreground: DEFAULT_FOREGROUND,
+        background: DEFAULT_BACKGROUND,
+        bold: false,
+    };
+
+    fn color_code(&self) -> ColorCode {
+        let foreground = if self.bold { self.foreground.bright() } else { self.foreground };
+        ColorCode::new(foreground, self.background)
+    }
+}
+
+// `ESC 7` 保存的光标位置和文字属性
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SavedCursor {
+    row: usize,
+    column: usize,
+    attributes: Attributes,
+}
+
+// 输出器，每个虚拟控制台一个
+// 写入的文本先经过ANSI转义序列解析器，颜色和光标控制序列在VGA上和在串口终端上效果一致
 pub struct Writer {
     row_position: usize,
     column_position: usize,
     color_code: ColorCode,
+    attributes: Attributes,
+    saved_cursor: Option<SavedCursor>,
+    parser: ansi::Parser,
+    // 硬件光标是否显示以及它的形状
+    cursor_visible: bool,
+    cursor_shape: CursorShape,
+    // 回滚缓冲区，`init_scrollback` 之前为 `None`
+    scrollback: Option<scrollback::Scrollback>,
+    // 是否为当前显示的控制台。只有活动控制台才会操作硬件光标
+    active: bool,
     // 静态生命周期引用当前VGA缓冲区 允许整个程序运行期间可变地访问这个Buffer
+    // 控制台在后台时指向它自己的后台缓冲区，切换到前台时换成VGA缓冲区
     buffer: &'static mut Buffer,
 }
 
 impl Writer {
+    fn new(buffer: &'static mut Buffer, active: bool) -> Writer {
+        Writer {
+            row_position: 0,
+            column_position: 0,
+            color_code: Attributes::DEFAULT.color_code(),
+            attributes: Attributes::DEFAULT,
+            saved_cursor: None,
+            parser: ansi::Parser::new(),
+            // BIOS留下的是显示中的下划线光标
+            cursor_visible: true,
+            cursor_shape: CursorShape::Underline,
+            scrollback: None,
+            active,
+            buffer,
+        }
+    }
+
     pub fn write_byte(&mut self, byte: u8) {
+        self.snap_back();
         match byte {
             0x08 => self.backspace(),
             b'\t' => self.horizontal_tab(),
             b'\n' => self.new_line(),
             b'\r' => self.carriage_return(),
-            byte => {
-                if self.column_position >= BUFFER_WIDTH {
-                    self.new_line()
-                }
-                let row = self.row_position.clone();
-                let col = self.column_position.clone();
-                let color_code = self.color_code.clone();
-                self.buffer.chars[row][col].write(ScreenChar {
-                    ascii_character: byte,
-                    color_code,
-                });
+            byte => self.write_glyph(byte),
+        }
+    }
 
-                self.column_position += 1;
-            }
+    // 在当前位置显示字库中编号为 `glyph` 的字形，不把它当作控制字符
+    fn write_glyph(&mut self, glyph: u8) {
+        if self.column_position >= BUFFER_WIDTH {
+            self.new_line()
         }
+        let row = self.row_position.clone();
+        let col = self.column_position.clone();
+        let color_code = self.color_code.clone();
+        self.buffer.chars[row][col].write(ScreenChar {
+            ascii_character: glyph,
+            color_code,
+        });
+
+        self.column_position += 1;
     }
 
+    // 写入字符串，其中的ANSI转义序列被解释执行。其他字符按UTF-8解码后转换为CP437字形显示，
+    // 制表符、重音字母、希腊字母等都能正常显示，字库中没有的字符每个显示为一个 `■`(0xfe)
+    // 写完后把硬件光标移到下一个字符将要出现的位置
+    // 正在翻看历史时，有新的输出就先回到底部
     pub fn write_string(&mut self, s: &str) {
-        for byte in s.bytes() {
-            match byte {
-                0x20..=0x7e | b'\n' | b'\r' | b'\t' | 0x08 => self.write_byte(byte),
-                _ => self.write_byte(0xfe),
+        self.snap_back();
+        for c in s.chars() {
+            if let Some(action) = self.parser.advance(c) {
+                self.perform(action);
             }
         }
+        self.update_cursor();
     }
 
-    fn clear_row(&mut self, row: usize) {
+    // 显示或隐藏硬件光标。后台控制台只记录设置，切换到前台时生效
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor_visible = visible;
+        self.restore_cursor();
+    }
+
+    // 修改硬件光标的形状，光标隐藏时在下次显示时生效
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) {
+        self.cursor_shape = shape;
+        self.restore_cursor();
+    }
+
+    // 向上(`lines` 为正)或向下翻看历史。翻看期间隐藏硬件光标，回到底部时恢复
+    pub fn scroll_view(&mut self, lines: isize) {
+        if let Some(scrollback) = self.scrollback.as_mut() {
+            scrollback.scroll(lines, self.buffer);
+            self.restore_cursor();
+        }
+    }
+
+    // 是否正在翻看历史
+    fn is_viewing(&self) -> bool {
+        self.scrollback.as_ref().is_some_and(|scrollback| scrollback.is_viewing())
+    }
+
+    // 正在翻看历史时回到底部，恢复实时画面和光标
+    fn snap_back(&mut self) {
+        if let Some(scrollback) = self.scrollback.as_mut() {
+            if scrollback.is_viewing() {
+                scrollback.snap_back(self.buffer);
+                self.restore_cursor();
+            }
+        }
+    }
+
+    // 按记录的设置重新设置硬件光标的显示、形状和位置
+    fn restore_cursor(&self) {
+        if !self.active {
+            return;
+        }
+        if self.cursor_visible && !self.is_viewing() {
+            cursor::show(self.cursor_shape);
+        } else {
+            cursor::hide();
+        }
+        self.update_cursor();
+    }
+
+    // 把硬件光标移到当前的写入位置。一行写满、等待换行时光标停在行尾的最后一个字符上
+    fn update_cursor(&self) {
+        if !self.active {
+            return;
+        }
+        let column = self.column_position.min(BUFFER_WIDTH - 1);
+        cursor::set_position((self.row_position * BUFFER_WIDTH + column) as u16);
+    }
+
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::Print(c) => match c {
+                '\n' | '\r' | '\t' | '\x08' => self.write_byte(c as u8),
+                c => self.write_glyph(cp437::encode(c).unwrap_or(cp437::REPLACEMENT)),
+            },
+            Action::SetGraphics(params) => self.set_graphics(&params),
+            Action::CursorPosition { row, column } => self.move_cursor(row, column),
+            Action::CursorUp(n) => self.move_cursor(self.row_position.saturating_sub(n), self.column_position),
+            Action::CursorDown(n) => self.move_cursor(self.row_position + n, self.column_position),
+            Action::CursorForward(n) => self.move_cursor(self.row_position, self.column_position + n),
+            Action::CursorBack(n) => self.move_cursor(self.row_position, self.column_position.saturating_sub(n)),
+            Action::EraseInLine(mode) => self.erase_in_line(mode),
+            Action::EraseInDisplay(mode) => self.erase_in_display(mode),
+            Action::SaveCursor => {
+                self.saved_cursor = Some(SavedCursor {
+                    row: self.row_position,
+                    column: self.column_position,
+                    attributes: self.attributes,
+                });
+            }
+            Action::RestoreCursor => {
+                // 没有保存过时回到左上角并使用默认属性，与VT100相同
+                let saved = self.saved_cursor.unwrap_or(SavedCursor {
+                    row: 0,
+                    column: 0,
+                    attributes: Attributes::DEFAULT,
+                });
+                self.move_cursor(saved.row, saved.column);
+                self.set_attributes(saved.attributes);
+            }
+            Action::ShowCursor(visible) => self.set_cursor_visible(visible),
+        }
+    }
+
+    // 按SGR参数修改文字属性，不支持的参数被忽略
+    fn set_graphics(&mut self, params: &Params) {
+        let mut attributes = self.attributes;
+        for param in params.iter() {
+            match param {
+                0 => attributes = Attributes::DEFAULT,
+                1 => attributes.bold = true,
+                22 => attributes.bold = false,
+                30..=37 => attributes.foreground = Color::from_ansi(param - 30, false),
+                39 => attributes.foreground = DEFAULT_FOREGROUND,
+                40..=47 => attributes.background = Color::from_ansi(param - 40, false),
+                49 => attributes.background = DEFAULT_BACKGROUND,
+                90..=97 => attributes.foreground = Color::from_ansi(param - 90, true),
+                100..=107 => attributes.background = Color::from_ansi(param - 100, true),
+                _ => {}
+            }
+        }
+        self.set_attributes(attributes);
+    }
+
+    fn set_attributes(&mut self, attributes: Attributes) {
+        self.attributes = attributes;
+        self.color_code = attributes.color_code();
+    }
+
+    // 把光标移动到指定位置，超出屏幕的部分被截断到边缘
+    fn move_cursor(&mut self, row: usize, column: usize) {
+        self.row_position = row.min(BUFFER_HEIGHT - 1);
+        self.column_position = column.min(BUFFER_WIDTH - 1);
+    }
+
+    // 用当前背景色的空格填充 `row` 行的 `columns` 列
+    fn clear_columns(&mut self, row: usize, columns: core::ops::Range<usize>) {
         let blank = ScreenChar {
             ascii_character: b' ',
             color_code: self.color_code,
         };
-        for col in 0..BUFFER_WIDTH {
-            self.buffer.chars[row.clone()][col].write(blank);
+        for col in columns {
+            self.buffer.chars[row][col].write(blank);
         }
     }
 
+    fn erase_in_line(&mut self, mode: EraseMode) {
+        let row = self.row_position;
+        // 光标可能停在行尾之后(等待换行)
+        let column = self.column_position.min(BUFFER_WIDTH - 1);
+        match mode {
+            EraseMode::ToEnd => self.clear_columns(row, column..BUFFER_WIDTH),
+            EraseMode::ToStart => self.clear_columns(row, 0..column + 1),
+            EraseMode::All => self.clear_row(row),
+        }
+    }
+
+    fn erase_in_display(&mut self, mode: EraseMode) {
+        self.erase_in_line(mode);
+        let rows = match mode {
+            EraseMode::ToEnd => self.row_position + 1..BUFFER_HEIGHT,
+            EraseMode::ToStart => 0..self.row_position,
+            EraseMode::All => 0..BUFFER_HEIGHT,
+        };
+        for row in rows {
+            self.clear_row(row);
+        }
+    }
+
+    fn clear_row(&mut self, row: usize) {
+        self.clear_columns(row, 0..BUFFER_WIDTH);
+    }
+
     fn new_line(&mut self) {
         self.row_position += 1;
         self.column_position = 0;
 
         if self.row_position >= BUFFER_HEIGHT {
+            // 移出顶部的一行保存到回滚缓冲区
+            if let Some(scrollback) = self.scrollback.as_mut() {
+                scrollback.push(scrollback::read_line(&self.buffer.chars[0]));
+            }
             // 向上滚屏
             for row in 0..BUFFER_HEIGHT - 1 {
                 for col in 0..BUFFER_WIDTH {
@@ -134,6 +435,8 @@ impl Writer {
                 }
             }
             self.clear_row(BUFFER_HEIGHT - 1);
+            // 滚屏后光标停留在最后一行，否则下一次写入会越界访问缓冲区
+            self.row_position = BUFFER_HEIGHT - 1;
         }
     }
 
@@ -156,19 +459,6 @@ impl Writer {
 
 }
 
-// 使用 `lazy_static` 宏定义一个全局静态变量 `WRITER`, 包含了多线程安全互斥锁 (Mutex)。内部保存了一个 `Writer` 结构体实例，用于向VGA缓冲区写入文本。
-// - VGA缓冲区的物理地址为 `0xb8000`，通过不安全（unsafe）转换成可变指针以便读写。
-// - 设置开始时光标位置和颜色代码。
-// - 因为访问裸指针和硬件资源是不安全的操作，所以需要unsafe块
-lazy_static! {
-    pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
-        row_position: 0,
-        column_position: 0,
-        color_code: ColorCode::new(Color::LightCyan, Color::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
-    });
-}
-
 impl fmt::Write for Writer {
     // 函数 `write_str` 返回一个 `Result` 类型，它是 Rust 中一种标准的返回类型用于包含可能存在的错误信息。`Result` 常常用来表示一个操作可能失败的情况，
     // 在这里它具体为 `Result<(), core::fmt::Error>`。
@@ -182,20 +472,82 @@ impl fmt::Write for Writer {
     }
 }
 
-// 定义函数 `_print` 来向VGA缓冲区输出格式化文本。使用 `core::fmt::Write` trait 的 `write_fmt` 方法。
+// 定义函数 `_print` 来向内核控制台输出格式化文本。使用 `core::fmt::Write` trait 的 `write_fmt` 方法。
 // - 使用了隐藏属性防止其出现在生成的文档中。
 // - 调用自定义的 `interrupts::without_interrupts` 函数来确保打印过程中不会被中断，避免死锁等并发问题。
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
+    _print_to(KERNEL_CONSOLE, args);
+}
+
+// 向第 `index` 个虚拟控制台输出格式化文本
+#[doc(hidden)]
+pub fn _print_to(index: usize, args: fmt::Arguments) {
     use core::fmt::Write;
 
     // 防止死锁
     interrupts::without_interrupts(||{
-        WRITER.lock().write_fmt(args).unwrap();
+        console(index).lock().write_fmt(args).unwrap();
     })
 }
 
+// 在panic处理函数中向内核控制台输出，并尽量把它切换到前台，不等待任何锁
+// 和串口一样，panic时内核控制台的锁仍被占用，说明持有者就是发生panic的这段代码，直接强制解锁。
+// 如果panic发生在切换控制台的中途，画面可能不完整，但信息总能写进内核控制台
+pub fn panic_print(args: fmt::Arguments) {
+    use core::fmt::Write;
+
+    interrupts::disable();
+    console::try_switch_console(KERNEL_CONSOLE);
+    let writer = console(KERNEL_CONSOLE);
+    if writer.is_locked() {
+        unsafe { writer.force_unlock() };
+    }
+    let _ = writer.lock().write_fmt(args);
+}
+
+// 在当前活动控制台的输出器上执行 `f`
+fn with_active<R>(f: impl FnOnce(&mut Writer) -> R) -> R {
+    interrupts::without_interrupts(|| f(&mut console(active_console()).lock()))
+}
+
+// 为每个控制台分配回滚缓冲区，此后滚出屏幕的行可以通过Shift+PageUp翻看
+// 需要在 `allocator::init_heap` 之后调用。在此之前的输出(如启动信息)不会被保存
+pub fn init_scrollback() {
+    for index in 0..CONSOLE_COUNT {
+        let lines = if index == KERNEL_CONSOLE { SCROLLBACK_LINES } else { CONSOLE_SCROLLBACK_LINES };
+        let scrollback = scrollback::Scrollback::new(lines);
+        interrupts::without_interrupts(|| console(index).lock().scrollback = Some(scrollback));
+    }
+}
+
+// 在活动控制台上向上翻看一页历史(Shift+PageUp)
+pub fn scroll_up() {
+    with_active(|writer| writer.scroll_view(SCROLL_PAGE));
+}
+
+// 在活动控制台上向下翻看一页历史(Shift+PageDown)，到底后回到实时画面
+pub fn scroll_down() {
+    with_active(|writer| writer.scroll_view(-SCROLL_PAGE));
+}
+
+// 显示活动控制台的硬件光标
+pub fn show_cursor() {
+    with_active(|writer| writer.set_cursor_visible(true));
+}
+
+// 隐藏活动控制台的硬件光标
+pub fn hide_cursor() {
+    with_active(|writer| writer.set_cursor_visible(false));
+}
+
+// 修改活动控制台的硬件光标形状(下划线或方块)
+pub fn set_cursor_shape(shape: CursorShape) {
+    with_active(|writer| writer.set_cursor_shape(shape));
+}
+
 // 定义了一个宏 `print!`, 当调用此宏时将展开成对上面定义的 `_print()` 函数的调用，传递给定参数作为格式化参数列表。这个宏可以在crate中任何地方使用
+// 输出总是写到内核控制台(第0个)，即使它当前不在前台
 #[macro_export]
 macro_rules! print {
     ($($arg:tt)*) => ($crate::vga_buffer::_print(format_args!($($arg)*)));
@@ -208,8 +560,163 @@ macro_rules! println {
     ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
 }
 
+// 向指定的虚拟控制台输出，例如 `console_println!(1, "shell> ")`
+#[macro_export]
+macro_rules! console_print {
+    ($console:expr, $($arg:tt)*) => ($crate::vga_buffer::_print_to($console, format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! console_println {
+    ($console:expr) => ($crate::console_print!($console, "\n"));
+    ($console:expr, $($arg:tt)*) => ($crate::console_print!($console, "{}\n", format_args!($($arg)*)));
+}
+
 pub fn print_something() {
     println!("Os start now.\n\n");
     println!("\t----Hello World From cjn's Operating System\n");
-    println!("\t\t\t\t\t\t\t\t2024.08.02\n");
+    // 启动时的日期和时间，来自CMOS实时时钟
+    println!("\t\t\t\t\t\t\t{}\n", crate::time::wall_clock());
+}
+
+#[test_case]
+fn test_println_simple() {
+    println!("test_println_simple output");
+}
+
+#[test_case]
+fn test_println_many() {
+    // 输出超过一屏的行数，验证滚屏不会越界
+    for _ in 0..200 {
+        println!("test_println_many output");
+    }
+}
+
+#[test_case]
+fn test_println_output() {
+    use core::fmt::Write;
+
+    let s = "Some test string that fits on a single line";
+    // 整个过程持有锁并关闭中断，防止中断处理函数或被切换进来的其他线程在中途打印而打乱屏幕内容
+    interrupts::without_interrupts(|| {
+        let mut writer = console(KERNEL_CONSOLE).lock();
+        // 先换行，确保字符串从行首开始写
+        writeln!(writer, "\n{}", s).expect("writeln failed");
+        // 写完后光标已换到下一行，所以字符串在上一行
+        let row = writer.row_position - 1;
+        for (i, c) in s.chars().enumerate() {
+            let screen_char = writer.buffer.chars[row][i].read();
+            assert_eq!(char::from(screen_char.ascii_character), c);
+        }
+    });
+}
+
+#[test_case]
+fn test_ansi_colors() {
+    use core::fmt::Write;
+
+    interrupts::without_interrupts(|| {
+        let mut writer = console(KERNEL_CONSOLE).lock();
+        // 粗体的红色显示为亮红色，SGR 0恢复默认颜色
+        write!(writer, "\n\x1b[1;31mR\x1b[44mB\x1b[0mN").expect("write failed");
+        let row = writer.row_position;
+        let chars = |col: usize| writer.buffer.chars[row][col].read();
+        assert_eq!(chars(0).ascii_character, b'R');
+        assert_eq!(chars(0).color_code, ColorCode::new(Color::LightRed, Color::Black));
+        assert_eq!(chars(1).color_code, ColorCode::new(Color::LightRed, Color::Blue));
+        assert_eq!(chars(2).color_code, Attributes::DEFAULT.color_code());
+        assert_eq!(writer.attributes, Attributes::DEFAULT);
+    });
+}
+
+#[test_case]
+fn test_write_cp437() {
+    use core::fmt::Write;
+
+    interrupts::without_interrupts(|| {
+        let mut writer = console(KERNEL_CONSOLE).lock();
+        // 每个字符占一格，字库中没有的字符(`中`)只显示一个替代字形
+        write!(writer, "\né┌─┐中x").expect("write failed");
+        let row = writer.row_position;
+        let glyphs: [u8; 6] = core::array::from_fn(|col| writer.buffer.chars[row][col].read().ascii_character);
+        assert_eq!(glyphs, [0x82, 0xda, 0xc4, 0xbf, 0xfe, b'x']);
+        assert_eq!(writer.column_position, 6);
+    });
+}
+
+#[test_case]
+fn test_ansi_cursor_and_erase() {
+    use core::fmt::Write;
+
+    interrupts::without_interrupts(|| {
+        let mut writer = console(KERNEL_CONSOLE).lock();
+        writeln!(writer).expect("write failed");
+        let (row, column) = (writer.row_position, writer.column_position);
+        // 保存光标，跳到第2行第5列写入，再恢复
+        write!(writer, "\x1b7\x1b[2;5HX\x1b[2DY\x1b8").expect("write failed");
+        assert_eq!(writer.buffer.chars[1][4].read().ascii_character, b'X');
+        assert_eq!(writer.buffer.chars[1][3].read().ascii_character, b'Y');
+        assert_eq!((writer.row_position, writer.column_position), (row, column));
+
+        // 回到行首擦除到行尾
+        write!(writer, "abc\r\x1b[K").expect("write failed");
+        assert_eq!(writer.buffer.chars[row][0].read().ascii_character, b' ');
+        assert_eq!(writer.buffer.chars[row][2].read().ascii_character, b' ');
+    });
+}
+
+#[test_case]
+fn test_hardware_cursor_follows_writer() {
+    use core::fmt::Write;
+
+    interrupts::without_interrupts(|| {
+        let mut writer = console(KERNEL_CONSOLE).lock();
+        write!(writer, "\nab").expect("write failed");
+        let expected = writer.row_position * BUFFER_WIDTH + 2;
+        assert_eq!(usize::from(cursor::position()), expected);
+
+        // 隐藏、修改形状后再显示
+        write!(writer, "\x1b[?25l").expect("write failed");
+        assert!(!cursor::is_visible());
+        writer.set_cursor_shape(CursorShape::Block);
+        assert!(!cursor::is_visible());
+        writer.set_cursor_visible(true);
+        assert!(cursor::is_visible());
+        writer.set_cursor_shape(CursorShape::Underline);
+    });
+}
+
+#[test_case]
+fn test_scrollback_view() {
+    use core::fmt::Write;
+
+    interrupts::without_interrupts(|| {
+        let mut writer = console(KERNEL_CONSOLE).lock();
+        // 先换行，确保从最后一行的行首开始写
+        writeln!(writer).expect("write failed");
+        for i in 0..BUFFER_HEIGHT {
+            writeln!(writer, "scrollback line {}", i).expect("write failed");
+        }
+        // 最后一行是空的等待输入行，第0行已经被滚出屏幕，成为最新的历史行
+        let row_text = |writer: &Writer, row: usize| {
+            let mut text = [0u8; 17];
+            for (i, c) in text.iter_mut().enumerate() {
+                *c = writer.buffer.chars[row][i].read().ascii_character;
+            }
+            text
+        };
+        assert_eq!(&row_text(&writer, 0), b"scrollback line 1");
+
+        writer.scroll_view(1);
+        assert_eq!(&row_text(&writer, 0), b"scrollback line 0");
+        assert_eq!(&row_text(&writer, 1), b"scrollback line 1");
+        writer.scroll_view(-1);
+        assert_eq!(&row_text(&writer, 0), b"scrollback line 1");
+
+        // 翻看时有新的输出会先回到实时画面
+        writer.scroll_view(SCROLL_PAGE);
+        write!(writer, "x").expect("write failed");
+        assert_eq!(&row_text(&writer, 0), b"scrollback line 1");
+        assert_eq!(writer.buffer.chars[BUFFER_HEIGHT - 1][0].read().ascii_character, b'x');
+    });
 }
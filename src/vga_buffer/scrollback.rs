@@ -0,0 +1,151 @@
+// 回滚缓冲区
+// 屏幕向上滚动时，移出顶部的行被保存到一个容量固定的环形队列里，满了之后丢弃最旧的行。
+// 翻看历史时先保存当前屏幕(实时画面)，再把历史和实时画面拼接起来的某一段绘制到VGA缓冲区；
+// 回到底部时恢复实时画面。队列在堆上分配，所以要等堆初始化之后才能开启。
+// 每个虚拟控制台各有一个队列。队列随输出逐渐增长，每次扩容为原来的两倍但不超过容量上限，堆空间不够时提前丢弃最旧的行
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use volatile::Volatile;
+
+use super::{Buffer, ScreenChar, BUFFER_HEIGHT, BUFFER_WIDTH};
+
+// 队列第一次扩容时至少分配的行数
+const MIN_GROWTH: usize = 16;
+
+// 屏幕上的一行
+pub(super) type Line = [ScreenChar; BUFFER_WIDTH];
+
+// 读出VGA缓冲区中的一行
+pub(super) fn read_line(row: &[Volatile<ScreenChar>; BUFFER_WIDTH]) -> Line {
+    core::array::from_fn(|col| row[col].read())
+}
+
+pub(super) struct Scrollback {
+    // 从旧到新排列的历史行
+    lines: VecDeque<Line>,
+    capacity: usize,
+    // 当前向上翻看了多少行，0表示显示实时画面
+    offset: usize,
+    // 开始翻看时保存的实时画面
+    live: Vec<Line>,
+}
+
+impl Scrollback {
+    // 最多保存 `capacity` 行。历史行的空间在需要时才分配
+    pub(super) fn new(capacity: usize) -> Self {
+        Scrollback {
+            lines: VecDeque::new(),
+            capacity,
+            offset: 0,
+            live: Vec::with_capacity(BUFFER_HEIGHT),
+        }
+    }
+
+    // 保存一行被滚出屏幕的内容
+    pub(super) fn push(&mut self, line: Line) {
+        // 队列满了，或者堆上分配不出更多空间时，丢弃最旧的一行腾出位置
+        let full = self.lines.len() == self.capacity || !self.reserve_line();
+        // 容量为0，或者一行都放不下时，什么也不保存
+        if full && self.lines.pop_front().is_none() {
+            return;
+        }
+        self.lines.push_back(line);
+    }
+
+    // 保证队列里还能再放一行，返回是否成功。只在 `len < capacity` 时调用
+    // 自己决定扩容的大小而不用 `VecDeque` 默认的翻倍，这样分配的空间最多正好是 `capacity` 行，不会被取整到2的幂
+    fn reserve_line(&mut self) -> bool {
+        if self.lines.len() < self.lines.capacity() {
+            return true;
+        }
+        let additional = self.lines.len().max(MIN_GROWTH).min(self.capacity - self.lines.len());
+        self.lines.try_reserve_exact(additional).is_ok()
+    }
+
+    // 是否正在翻看历史
+    pub(super) fn is_viewing(&self) -> bool {
+        self.offset > 0
+    }
+
+    // 向上(`lines` 为正)或向下翻看 `lines` 行，最多翻到最旧的历史行，翻回0时恢复实时画面
+    pub(super) fn scroll(&mut self, lines: isize, buffer: &mut Buffer) {
+        let offset = self.offset.saturating_add_signed(lines).min(self.lines.len());
+        if offset == self.offset {
+            return;
+        }
+        if !self.is_viewing() {
+            self.live.clear();
+            self.live.extend(buffer.chars.iter().map(read_line));
+        }
+        self.offset = offset;
+        if self.is_viewing() {
+            self.render(buffer);
+        } else {
+            self.restore(buffer);
+        }
+    }
+
+    // 回到底部，恢复实时画面
+    pub(super) fn snap_back(&mut self, buffer: &mut Buffer) {
+        if self.is_viewing() {
+            self.offset = 0;
+            self.restore(buffer);
+        }
+    }
+
+    // 把历史行和实时画面看作一个整体，绘制末尾向上偏移 `offset` 行的一屏
+    fn render(&self, buffer: &mut Buffer) {
+        let history = self.lines.len();
+        for (row, chars) in buffer.chars.iter_mut().enumerate() {
+            let index = history + row - self.offset;
+            let line = if index < history {
+                &self.lines[index]
+            } else {
+                &self.live[index - history]
+            };
+            for (col, c) in chars.iter_mut().enumerate() {
+                c.write(line[col]);
+            }
+        }
+    }
+
+    fn restore(&mut self, buffer: &mut Buffer) {
+        for (chars, line) in buffer.chars.iter_mut().zip(self.live.iter()) {
+            for (c, screen_char) in chars.iter_mut().zip(line.iter()) {
+                c.write(*screen_char);
+            }
+        }
+        self.live.clear();
+    }
+}
+
+#[test_case]
+fn test_scrollback_ring_drops_oldest() {
+    use super::ColorCode;
+
+    let line = |c: u8| {
+        [ScreenChar {
+            ascii_character: c,
+            color_code: ColorCode(0),
+        }; BUFFER_WIDTH]
+    };
+    let mut scrollback = Scrollback::new(3);
+    for c in b'a'..=b'e' {
+        scrollback.push(line(c));
+    }
+    assert_eq!(scrollback.lines.len(), 3);
+    assert_eq!(scrollback.lines[0][0].ascii_character, b'c');
+    assert_eq!(scrollback.lines[2][0].ascii_character, b'e');
+}
+
+#[test_case]
+fn test_scrollback_growth_bounded() {
+    let mut scrollback = Scrollback::new(40);
+    for _ in 0..100 {
+        scrollback.push([ScreenChar::BLANK; BUFFER_WIDTH]);
+    }
+    assert_eq!(scrollback.lines.len(), 40);
+    // 16 -> 32 -> 40，不会取整到64
+    assert!(scrollback.lines.capacity() < 64);
+}
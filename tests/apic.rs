@@ -0,0 +1,55 @@
+// APIC测试：从8259 PIC切换到APIC并使用本地APIC定时器后，定时器中断应该继续到达，时间也能正常流逝
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(cjn_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use core::time::Duration;
+use cjn_os::interrupts::apic::{self, TimerSource};
+use cjn_os::interrupts::{self, Controller};
+use cjn_os::time;
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    cjn_os::init();
+    unsafe { cjn_os::memory::init(boot_info) };
+    cjn_os::allocator::init_heap().expect("heap initialization failed");
+    apic::init(TimerSource::LocalApic).expect("APIC initialization failed");
+
+    test_main();
+    cjn_os::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cjn_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn apic_is_active() {
+    assert!(apic::is_supported());
+    assert_eq!(interrupts::controller(), Controller::Apic);
+}
+
+#[test_case]
+fn local_apic_timer_ticks() {
+    let start = time::ticks();
+    // 10个tick约100毫秒，如果定时器中断没有到达或EOI没有发送，这里会一直等下去直到超时
+    while time::ticks() < start + 10 {
+        x86_64::instructions::hlt();
+    }
+}
+
+#[test_case]
+fn local_apic_timer_period() {
+    // 校准后的周期与配置的频率相差不到10%
+    let expected = 1_000_000_000 / u64::from(time::TIMER_FREQUENCY_HZ);
+    assert!(time::tick_period_ns().abs_diff(expected) * 10 < expected);
+    let start = time::uptime();
+    time::sleep_ms(50);
+    assert!(time::uptime() - start >= Duration::from_millis(50));
+}
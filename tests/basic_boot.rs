@@ -0,0 +1,26 @@
+// 基本启动测试：在不调用 `cjn_os::init()` 的情况下验证 `_start` 之后立即可以打印输出
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(cjn_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use core::panic::PanicInfo;
+use cjn_os::println;
+
+#[no_mangle] // 不重整函数名
+pub extern "C" fn _start() -> ! {
+    test_main();
+
+    cjn_os::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cjn_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn test_println() {
+    println!("test_println output");
+}
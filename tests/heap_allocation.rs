@@ -0,0 +1,94 @@
+// 堆分配测试：初始化内存管理和内核堆之后，验证 `alloc` crate 中的常用类型能正常工作，并且释放的内存能被重复使用
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(cjn_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use cjn_os::allocator::HEAP_SIZE;
+
+entry_point!(main);
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    cjn_os::init();
+    unsafe { cjn_os::memory::init(boot_info) };
+    cjn_os::allocator::init_heap().expect("heap initialization failed");
+
+    test_main();
+    cjn_os::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cjn_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn simple_allocation() {
+    let heap_value_1 = Box::new(41);
+    let heap_value_2 = Box::new(13);
+    assert_eq!(*heap_value_1, 41);
+    assert_eq!(*heap_value_2, 13);
+}
+
+#[test_case]
+fn large_vec() {
+    let n = 1000;
+    let mut vec = Vec::new();
+    for i in 0..n {
+        vec.push(i);
+    }
+    assert_eq!(vec.iter().sum::<u64>(), (n - 1) * n / 2);
+}
+
+#[test_case]
+fn string_and_btree_map() {
+    let mut map = BTreeMap::new();
+    for i in 0..100 {
+        let mut s = String::from("key");
+        s.push_str(if i % 2 == 0 { "-even" } else { "-odd" });
+        map.insert(i, s);
+    }
+    assert_eq!(map.len(), 100);
+    assert_eq!(map[&42], "key-even");
+    assert_eq!(map[&7], "key-odd");
+}
+
+#[test_case]
+fn reference_counting() {
+    let shared = Rc::new(Vec::from([1, 2, 3]));
+    let cloned = shared.clone();
+    assert_eq!(Rc::strong_count(&shared), 2);
+    drop(cloned);
+    assert_eq!(Rc::strong_count(&shared), 1);
+}
+
+#[test_case]
+fn many_boxes() {
+    // 分配次数远超过堆的大小，只有释放的内存被重新利用时才能通过
+    for i in 0..HEAP_SIZE {
+        let x = Box::new(i);
+        assert_eq!(*x, i);
+    }
+}
+
+// 碰撞分配器只有在所有分配都释放后才能重用内存，`long_lived` 一直存活会导致堆耗尽，所以该测试不适用于它
+#[cfg(not(feature = "alloc-bump"))]
+#[test_case]
+fn many_boxes_long_lived() {
+    let long_lived = Box::new(1);
+    for i in 0..HEAP_SIZE {
+        let x = Box::new(i);
+        assert_eq!(*x, i);
+    }
+    assert_eq!(*long_lived, 1);
+}
@@ -0,0 +1,29 @@
+// should_panic测试：测试函数必须panic才算通过，返回则视为失败
+// Cargo.toml中对该测试设置了 `harness = false`，由 `_start` 直接调用唯一的测试函数
+#![no_std]
+#![no_main]
+
+use core::panic::PanicInfo;
+use cjn_os::{exit_qemu, serial_print, serial_println, QemuExitCode};
+
+#[no_mangle] // 不重整函数名
+pub extern "C" fn _start() -> ! {
+    should_fail();
+    // 执行到这里说明没有发生panic，测试失败
+    serial_println!("[test did not panic]");
+    exit_qemu(QemuExitCode::Failed);
+    cjn_os::hlt_loop();
+}
+
+fn should_fail() {
+    serial_print!("should_panic::should_fail...\t");
+    assert_eq!(0, 1);
+}
+
+// 发生panic说明测试通过，以成功码退出QEMU
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    serial_println!("[ok]");
+    exit_qemu(QemuExitCode::Success);
+    cjn_os::hlt_loop();
+}
@@ -0,0 +1,64 @@
+// 栈溢出测试：无限递归导致内核栈溢出，触及保护页后CPU应该切换到 `DOUBLE_FAULT_IST_INDEX` 对应的独立栈上执行双重异常处理函数，而不是三重异常重启
+// Cargo.toml中对该测试设置了 `harness = false`，因为双重异常处理函数无法返回测试运行器
+#![no_std]
+#![no_main]
+#![feature(abi_x86_interrupt)]
+
+use core::panic::PanicInfo;
+use lazy_static::lazy_static;
+use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
+use cjn_os::{exit_qemu, serial_print, serial_println, QemuExitCode};
+
+#[no_mangle] // 不重整函数名
+pub extern "C" fn _start() -> ! {
+    serial_print!("stack_overflow::stack_overflow...\t");
+
+    // 只加载GDT(包括设置好双重异常栈的TSS)，不初始化PIC和开启中断，避免定时器中断干扰测试
+    cjn_os::gdt::init();
+    init_test_idt();
+
+    // 触发栈溢出
+    stack_overflow();
+
+    panic!("Execution continued after stack overflow");
+}
+
+#[allow(unconditional_recursion)]
+fn stack_overflow() {
+    // 每次递归都会把返回地址压栈
+    stack_overflow();
+    // 防止尾递归优化把递归变成循环
+    volatile::Volatile::new(0).read();
+}
+
+// 测试专用的IDT：与内核IDT相同地为双重异常指定独立栈，但处理函数改为报告测试成功
+lazy_static! {
+    static ref TEST_IDT: InterruptDescriptorTable = {
+        let mut idt = InterruptDescriptorTable::new();
+        unsafe {
+            idt.double_fault
+                .set_handler_fn(test_double_fault_handler)
+                .set_stack_index(cjn_os::gdt::DOUBLE_FAULT_IST_INDEX);
+        }
+        idt
+    };
+}
+
+pub fn init_test_idt() {
+    TEST_IDT.load();
+}
+
+// 能进入这里说明CPU成功切换到了双重异常栈
+extern "x86-interrupt" fn test_double_fault_handler(
+    _stack_frame: InterruptStackFrame,
+    _error_code: u64,
+) -> ! {
+    serial_println!("[ok]");
+    exit_qemu(QemuExitCode::Success);
+    cjn_os::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cjn_os::test_panic_handler(info)
+}